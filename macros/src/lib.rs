@@ -0,0 +1,176 @@
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse::Parser, parse_macro_input, punctuated::Punctuated, Error, Expr, ExprLit, ItemFn, Lit,
+    MetaNameValue, ReturnType, Token,
+};
+
+// documented on its re-export in `async_instrumenter`
+#[proc_macro_attribute]
+pub fn instrument_async(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = match Punctuated::<MetaNameValue, Token![,]>::parse_terminated.parse(args) {
+        Ok(args) => args,
+        Err(e) => return e.into_compile_error().into(),
+    };
+
+    let item = parse_macro_input!(item as ItemFn);
+
+    match expand(args, item) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+fn expand(args: Punctuated<MetaNameValue, Token![,]>, item: ItemFn) -> syn::Result<TokenStream2> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+
+    if sig.asyncness.is_none() {
+        return Err(Error::new_spanned(
+            sig.fn_token,
+            "#[instrument_async] can only be used on async functions",
+        ));
+    }
+
+    let mut options = TokenStream2::new();
+    let mut name = None;
+    let mut message = None;
+    let mut krate = None;
+
+    for arg in args {
+        let key = match arg.path.get_ident() {
+            Some(key) => key.to_string(),
+            None => return Err(Error::new_spanned(arg.path, "expected an option name")),
+        };
+        let value = arg.value;
+
+        match key.as_str() {
+            "target" => options.extend(quote!(target: #value,)),
+            "level" => options.extend(quote!(#value,)),
+            "threshold" => options.extend(quote!(threshold = #value,)),
+            "escalate" => options.extend(quote!(escalate = #value,)),
+            "labels" => {
+                check_label_keys(&value)?;
+                options.extend(quote!(labels = #value,));
+            }
+            "name" => name = Some(value),
+            "crate" => match value {
+                Expr::Path(path) => krate = Some(path.into_token_stream()),
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(lit), ..
+                }) => krate = Some(lit.parse::<syn::Path>()?.into_token_stream()),
+                value => return Err(Error::new_spanned(value, "crate must be a path")),
+            },
+            "message" => match value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(lit), ..
+                }) => message = Some(lit),
+                value => return Err(Error::new_spanned(value, "message must be a string literal")),
+            },
+            _ => {
+                return Err(Error::new_spanned(
+                    arg.path,
+                    "unknown option, expected one of `target`, `level`, `threshold`, `escalate`, `labels`, `name`, `message` or `crate`",
+                ))
+            }
+        }
+    }
+
+    let name = match name {
+        Some(name) => name.into_token_stream(),
+        None => {
+            let ident = sig.ident.to_string();
+            quote!(::core::concat!(::core::module_path!(), "::", #ident))
+        }
+    };
+
+    let message = message.map(|message| quote!(#message,));
+
+    let krate = krate.unwrap_or_else(|| quote!(::async_instrumenter));
+
+    // the body moves into an async block, so tell it the return type up front to keep
+    // inference of e.g. `?` working the same as it did in the function itself
+    let return_hint = match &sig.output {
+        ReturnType::Default => Some(quote!(())),
+        ReturnType::Type(_, ty) if !contains_impl(ty.to_token_stream()) => {
+            Some(ty.to_token_stream())
+        }
+        ReturnType::Type(..) => None,
+    }
+    .map(|ty| {
+        quote! {
+            #[allow(unreachable_code, clippy::diverging_sub_expression, clippy::needless_return)]
+            if false {
+                let __instrument_async_return: #ty = loop {};
+                return __instrument_async_return;
+            }
+        }
+    });
+
+    let stmts = block.stmts;
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            #krate::instrument!(#options name = #name, #message async move {
+                #return_hint
+                #(#stmts)*
+            })
+            .await
+        }
+    })
+}
+
+/// Label keys added on export by `async_instrumenter`, which user labels would clash with
+const RESERVED_LABEL_KEYS: [&str; 6] = ["site", "le", "name", "outcome", "file", "line"];
+
+/// Reject the label keys `instrument!` would, pointing at the offending key
+///
+/// Anything but an array of `key = value` pairs is left for `instrument!` to complain about.
+fn check_label_keys(labels: &Expr) -> syn::Result<()> {
+    let Expr::Array(labels) = labels else {
+        return Ok(());
+    };
+
+    for label in &labels.elems {
+        let Expr::Assign(label) = label else {
+            continue;
+        };
+        let Expr::Path(key) = &*label.left else {
+            continue;
+        };
+        let Some(key) = key.path.get_ident() else {
+            continue;
+        };
+
+        let name = key.to_string();
+        if name.starts_with("__") {
+            return Err(Error::new_spanned(
+                key,
+                format!("invalid label key `{name}`, it must not start with `__`"),
+            ));
+        }
+        if RESERVED_LABEL_KEYS.contains(&name.as_str()) {
+            return Err(Error::new_spanned(
+                key,
+                format!("label key `{name}` is reserved, as it's added on export"),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the type contains `impl Trait`, which can't be written out in a `let`
+fn contains_impl(tokens: TokenStream2) -> bool {
+    tokens.into_iter().any(|tt| match tt {
+        TokenTree::Ident(ident) => ident == "impl",
+        TokenTree::Group(group) => contains_impl(group.stream()),
+        _ => false,
+    })
+}
@@ -0,0 +1,63 @@
+use std::{
+    io,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Handle to a [`Reporter`](crate::Reporter) or [`Watchdog`](crate::Watchdog) running on a background
+/// thread, stopping it when dropped
+#[derive(Debug)]
+pub struct BackgroundHandle {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BackgroundHandle {
+    /// Stop the background thread and wait for it to exit
+    pub fn stop(mut self) {
+        self.stop.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for BackgroundHandle {
+    fn drop(&mut self) {
+        // dropping the sender wakes up the thread, which then exits on its own
+        self.stop.take();
+    }
+}
+
+/// Run `tick` every `interval` on a thread called `name` until the returned handle stops it
+///
+/// A zero interval is rejected, since the thread would do nothing but tick.
+pub(crate) fn spawn_periodic(
+    name: &str,
+    interval: Duration,
+    mut tick: impl FnMut() + Send + 'static,
+) -> io::Result<BackgroundHandle> {
+    if interval.is_zero() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the interval must not be zero",
+        ));
+    }
+
+    let (stop, stopped) = mpsc::channel::<()>();
+
+    let thread = thread::Builder::new()
+        .name(name.to_owned())
+        .spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                tick();
+            }
+        })?;
+
+    Ok(BackgroundHandle {
+        stop: Some(stop),
+        thread: Some(thread),
+    })
+}
@@ -0,0 +1,118 @@
+use std::{cell::Cell, time::Duration, time::Instant};
+
+/// A source of time used by [`InstrumentFuture`](crate::InstrumentFuture) to take its measurements
+///
+/// The implementing type is the point in time itself, just like [`Instant`] is. This is what
+/// [`InstrumentFuture`](crate::InstrumentFuture) uses by default, [`MockClock`] can be used
+/// instead to get deterministic measurements in tests.
+pub trait Clock: Copy {
+    /// The current point in time
+    fn now() -> Self;
+
+    /// The amount of time elapsed from `earlier` to `self`, or zero if `earlier` is later than `self`
+    fn duration_since(&self, earlier: Self) -> Duration;
+}
+
+impl Clock for Instant {
+    fn now() -> Self {
+        Instant::now()
+    }
+
+    fn duration_since(&self, earlier: Self) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+}
+
+thread_local! {
+    static MOCK_NOW: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+/// A manually advanced [`Clock`] for deterministic tests
+///
+/// Time only moves forward when [`MockClock::advance`] is called. The time is tracked per thread,
+/// so each test gets its own independent clock as long as the instrumented future is polled on the
+/// test's thread.
+///
+/// ```rust
+/// # use std::{future::Future, pin::pin, task::{Context, Poll, Waker}, time::Duration};
+/// use async_instrumenter::{InstrumentFuture, MockClock};
+///
+/// let mut polled = false;
+/// let fut = std::future::poll_fn(|_| {
+///     // every poll does 10ms of work
+///     MockClock::advance(Duration::from_millis(10));
+///
+///     if polled {
+///         Poll::Ready(())
+///     } else {
+///         polled = true;
+///         Poll::Pending
+///     }
+/// });
+///
+/// let mut fut = pin!(InstrumentFuture::<_, MockClock>::with_clock(fut));
+/// let mut cx = Context::from_waker(Waker::noop());
+///
+/// assert!(fut.as_mut().poll(&mut cx).is_pending());
+///
+/// // wait 100ms for the next wakeup
+/// MockClock::advance(Duration::from_millis(100));
+///
+/// let Poll::Ready(res) = fut.as_mut().poll(&mut cx) else {
+///     unreachable!()
+/// };
+///
+/// assert_eq!(res.elapsed, Duration::from_millis(120));
+/// assert_eq!(res.busy, Duration::from_millis(20));
+/// assert_eq!(res.idle, Duration::from_millis(100));
+/// assert_eq!(res.polls, 2);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MockClock(Duration);
+
+impl MockClock {
+    /// Move the current thread's clock forward
+    pub fn advance(by: Duration) {
+        MOCK_NOW.with(|now| now.set(now.get() + by));
+    }
+
+    /// Set the current thread's clock to `since_start` after its starting point
+    pub fn set(since_start: Duration) {
+        MOCK_NOW.with(|now| now.set(since_start));
+    }
+
+    /// How far the current thread's clock has been advanced from its starting point
+    pub fn elapsed() -> Duration {
+        MOCK_NOW.with(Cell::get)
+    }
+}
+
+impl Clock for MockClock {
+    fn now() -> Self {
+        MockClock(Self::elapsed())
+    }
+
+    fn duration_since(&self, earlier: Self) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+}
+
+/// The CPU time consumed by the calling thread so far, if the platform supports measuring it
+#[cfg(unix)]
+pub(crate) fn thread_cpu_time() -> Option<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+
+    // SAFETY: `ts` is a valid, writable timespec for the duration of the call
+    let res = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+
+    (res == 0).then(|| Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+/// The CPU time consumed by the calling thread so far, if the platform supports measuring it
+#[cfg(not(unix))]
+pub(crate) fn thread_cpu_time() -> Option<Duration> {
+    None
+}
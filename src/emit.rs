@@ -0,0 +1,352 @@
+//! Reporting done by the crate itself rather than through the macros, which goes
+//! through `log` or `tracing` depending on the enabled features
+
+use std::{
+    backtrace::Backtrace,
+    fmt::{self, Debug, Display},
+    panic::Location,
+    time::Duration,
+};
+
+use log::Level;
+
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+use crate::InstrumentIoResult;
+use crate::{InFlightFuture, InstrumentFutureCancelled, InstrumentFutureResult};
+
+const TARGET: &str = "async_instrumenter";
+
+/// Emit a `tracing` event at a runtime level under the crate's target
+#[cfg(feature = "tracing")]
+macro_rules! event {
+    ($level:expr, $($args:tt)+) => {
+        crate::_event!(target: TARGET, $level, $($args)+)
+    };
+}
+
+/// How an instrumented site is shown in a log message, its name if it has one or its file and line otherwise,
+/// followed by its labels if it has any
+#[derive(Debug, Clone, Copy)]
+pub struct Site<'a> {
+    name: Option<&'a str>,
+    file: &'a str,
+    line: u32,
+    labels: &'a [(&'static str, String)],
+    always_located: bool,
+}
+
+impl<'a> Site<'a> {
+    pub fn new(name: Option<&'a str>, file: &'a str, line: u32) -> Self {
+        Self {
+            name,
+            file,
+            line,
+            labels: &[],
+            always_located: false,
+        }
+    }
+
+    pub fn labels(mut self, labels: &'a [(&'static str, String)]) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Show the file and line after the name too, e.g. `fetch_user (src/users.rs:12)`
+    pub fn always_located(mut self) -> Self {
+        self.always_located = true;
+        self
+    }
+}
+
+impl Display for Site<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) if self.always_located => write!(f, "{name} ({}:{})", self.file, self.line)?,
+            Some(name) => f.write_str(name)?,
+            None => write!(f, "{}:{}", self.file, self.line)?,
+        }
+
+        if self.labels.is_empty() {
+            Ok(())
+        } else {
+            Display::fmt(&Labels(self.labels), f)
+        }
+    }
+}
+
+/// Label keys added by the exporters themselves, which user labels would clash with
+const RESERVED_LABEL_KEYS: [&str; 6] = ["site", "le", "name", "outcome", "file", "line"];
+
+/// Whether `key` is a Prometheus label name, `[A-Za-z_][A-Za-z0-9_]*` without the `__` prefix reserved for
+/// internal use
+///
+/// `const` so the macros can check their label keys at compile time.
+pub const fn is_valid_label_key(key: &str) -> bool {
+    let key = key.as_bytes();
+    if key.is_empty()
+        || key[0].is_ascii_digit()
+        || (key.len() > 1 && key[0] == b'_' && key[1] == b'_')
+    {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < key.len() {
+        if !key[i].is_ascii_alphanumeric() && key[i] != b'_' {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+/// Whether `key` is one of the [reserved keys](RESERVED_LABEL_KEYS)
+pub const fn is_reserved_label_key(key: &str) -> bool {
+    let mut i = 0;
+    while i < RESERVED_LABEL_KEYS.len() {
+        if bytes_eq(key.as_bytes(), RESERVED_LABEL_KEYS[i].as_bytes()) {
+            return true;
+        }
+        i += 1;
+    }
+
+    false
+}
+
+/// `==` for byte strings, which isn't `const`
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+/// Panic unless `key` is valid as a label key everywhere labels are exported, see [`is_valid_label_key`] and
+/// [`is_reserved_label_key`]
+#[track_caller]
+pub(crate) fn check_label_key(key: &str) {
+    assert!(
+        is_valid_label_key(key),
+        "invalid label key `{key}`, it must match `[A-Za-z_][A-Za-z0-9_]*` and not start with `__`"
+    );
+    assert!(
+        !is_reserved_label_key(key),
+        "label key `{key}` is reserved, as it's added on export"
+    );
+}
+
+/// How labels are shown in log messages and `tracing` fields, e.g. `{endpoint="/users", tenant="42"}`
+#[derive(Debug, Clone, Copy)]
+pub struct Labels<'a, K = &'static str, V = String>(pub &'a [(K, V)]);
+
+impl<K: Display, V: Debug> Display for Labels<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("{")?;
+
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+
+            write!(f, "{key}={value:?}")?;
+        }
+
+        f.write_str("}")
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn completed<R>(level: Level, location: &Location<'_>, res: &InstrumentFutureResult<R>) {
+    log::log!(
+        target: TARGET,
+        level,
+        "{} completed in {:?} (busy {:?}, idle {:?}, {} polls)",
+        Site::new(res.name, location.file(), location.line()).labels(&res.labels),
+        res.elapsed,
+        res.busy,
+        res.idle,
+        res.polls
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn completed<R>(level: Level, location: &Location<'_>, res: &InstrumentFutureResult<R>) {
+    event!(
+        level,
+        name = res.name,
+        file = location.file(),
+        line = location.line(),
+        labels = %Labels(&res.labels),
+        elapsed = ?res.elapsed,
+        busy = ?res.busy,
+        idle = ?res.idle,
+        polls = res.polls,
+        "completed"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn cancelled(
+    level: Level,
+    location: &Location<'_>,
+    cancelled: &InstrumentFutureCancelled,
+) {
+    log::log!(
+        target: TARGET,
+        level,
+        "{} cancelled after {:?} ({} polls)",
+        Site::new(cancelled.name, location.file(), location.line()).labels(&cancelled.labels),
+        cancelled.elapsed,
+        cancelled.polls
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn cancelled(
+    level: Level,
+    location: &Location<'_>,
+    cancelled: &InstrumentFutureCancelled,
+) {
+    event!(
+        level,
+        name = cancelled.name,
+        file = location.file(),
+        line = location.line(),
+        labels = %Labels(&cancelled.labels),
+        elapsed = ?cancelled.elapsed,
+        busy = ?cancelled.busy,
+        idle = ?cancelled.idle,
+        polls = cancelled.polls,
+        "cancelled"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn blocking_poll(
+    site: Site<'_>,
+    poll: Duration,
+    budget: Duration,
+    backtrace: Option<&Backtrace>,
+) {
+    // blocking code has to be tracked down, which a name alone doesn't point to
+    let site = site.always_located();
+
+    match backtrace {
+        Some(backtrace) => log::warn!(
+            target: TARGET,
+            "{site} blocked for {poll:?} in a single poll, over its budget of {budget:?}, polled from:\n{backtrace}"
+        ),
+        None => log::warn!(
+            target: TARGET,
+            "{site} blocked for {poll:?} in a single poll, over its budget of {budget:?}"
+        ),
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn blocking_poll(
+    site: Site<'_>,
+    poll: Duration,
+    budget: Duration,
+    backtrace: Option<&Backtrace>,
+) {
+    tracing::warn!(
+        target: TARGET,
+        name = site.name,
+        file = site.file,
+        line = site.line,
+        labels = %Labels(site.labels),
+        poll = ?poll,
+        budget = ?budget,
+        backtrace = backtrace.map(tracing::field::display),
+        "blocking poll"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn stuck(level: Level, future: &InFlightFuture) {
+    log::log!(
+        target: TARGET,
+        level,
+        "{} pending for {:?} ({} polls, last poll {:?} ago)",
+        Site::new(future.name, future.location.file(), future.location.line()).labels(&future.labels),
+        future.age,
+        future.polls,
+        future.since_last_poll
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn stuck(level: Level, future: &InFlightFuture) {
+    event!(
+        level,
+        name = future.name,
+        file = future.location.file(),
+        line = future.location.line(),
+        labels = %Labels(&future.labels),
+        age = ?future.age,
+        since_last_poll = ?future.since_last_poll,
+        polls = future.polls,
+        "stuck"
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn report(level: Level, report: &str) {
+    log::log!(target: TARGET, level, "{report}");
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn report(level: Level, report: &str) {
+    event!(level, "{report}");
+}
+
+#[cfg(all(
+    any(feature = "tokio", feature = "futures-io"),
+    not(feature = "tracing")
+))]
+pub(crate) fn io(level: Level, location: &Location<'_>, res: &InstrumentIoResult) {
+    log::log!(
+        target: TARGET,
+        level,
+        "{} transferred {} bytes read ({:.0} B/s) and {} bytes written ({:.0} B/s) in {:?} (busy {:?}, blocked {:?}, {} pending)",
+        Site::new(res.name, location.file(), location.line()),
+        res.bytes_read,
+        res.read_throughput(),
+        res.bytes_written,
+        res.write_throughput(),
+        res.elapsed,
+        res.busy,
+        res.blocked,
+        res.pending
+    );
+}
+
+#[cfg(all(any(feature = "tokio", feature = "futures-io"), feature = "tracing"))]
+pub(crate) fn io(level: Level, location: &Location<'_>, res: &InstrumentIoResult) {
+    event!(
+        level,
+        name = res.name,
+        file = location.file(),
+        line = location.line(),
+        elapsed = ?res.elapsed,
+        bytes_read = res.bytes_read,
+        bytes_written = res.bytes_written,
+        read_throughput = res.read_throughput(),
+        write_throughput = res.write_throughput(),
+        busy = ?res.busy,
+        blocked = ?res.blocked,
+        pending = res.pending,
+        "io report"
+    );
+}
@@ -0,0 +1,85 @@
+use std::{
+    future::Future,
+    panic::Location,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use log::Level;
+use pin_project::pin_project;
+
+use crate::{emit, InstrumentFuture};
+
+/// Adapters to instrument any [`Future`] without breaking a method chain
+///
+/// ```rust
+/// # use async_instrumenter::InstrumentExt;
+/// # use log::Level;
+/// # async fn fetch_user() -> u32 { 0 }
+/// # async fn run() {
+/// let res = fetch_user().instrumented_named("fetch_user").await;
+/// println!("{} took {:?}", res.name.unwrap(), res.elapsed);
+///
+/// // logs the elapsed time and resolves to the output of `fetch_user()`
+/// let user: u32 = fetch_user().log_elapsed(Level::Info).await;
+/// # }
+/// ```
+pub trait InstrumentExt: Future + Sized {
+    /// Wrap the future in an [`InstrumentFuture`]
+    #[track_caller]
+    fn instrumented(self) -> InstrumentFuture<Self> {
+        InstrumentFuture::new(self)
+    }
+
+    /// Wrap the future in an [`InstrumentFuture`] with the given [name](InstrumentFuture::name)
+    #[track_caller]
+    fn instrumented_named(self, name: &'static str) -> InstrumentFuture<Self> {
+        InstrumentFuture::new(self).name(name)
+    }
+
+    /// Log how long the future took to execute at `level`, resolving to the future's own output
+    ///
+    /// Like [`instrument!`](crate::instrument), the caller's file and line are part of the message, and
+    /// a message is also logged if the future gets cancelled after it was polled. The message is logged
+    /// with the `async_instrumenter` target.
+    #[track_caller]
+    fn log_elapsed(self, level: Level) -> LogElapsed<Self> {
+        let location = Location::caller();
+
+        LogElapsed {
+            future: InstrumentFuture::new(self).on_cancel(move |cancelled| {
+                // a future dropped before it ever ran wasn't cut short, there's nothing to report
+                if cancelled.polls > 0 {
+                    emit::cancelled(level, location, &cancelled);
+                }
+            }),
+            level,
+            location,
+        }
+    }
+}
+
+impl<F: Future> InstrumentExt for F {}
+
+/// Future returned by [`InstrumentExt::log_elapsed`]
+#[derive(Debug)]
+#[pin_project]
+pub struct LogElapsed<F: Future> {
+    #[pin]
+    future: InstrumentFuture<F>,
+    level: Level,
+    location: &'static Location<'static>,
+}
+
+impl<F: Future> Future for LogElapsed<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+
+        this.future.poll(cx).map(|timed| {
+            emit::completed(*this.level, this.location, &timed);
+            timed.result
+        })
+    }
+}
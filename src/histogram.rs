@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Every power of two range of values is split into `2^SUB_BITS` linear sub-buckets,
+/// which bounds the relative error of a bucket to about 3%
+const SUB_BITS: u32 = 5;
+const SUB_BUCKETS: usize = 1 << SUB_BITS;
+
+/// Values up to `2^MAX_MAGNITUDE` ns (a bit over 2 hours) get their own bucket, anything larger
+/// is counted in the last one
+const MAX_MAGNITUDE: u32 = 43;
+
+const BUCKETS: usize = (MAX_MAGNITUDE - SUB_BITS + 2) as usize * SUB_BUCKETS;
+
+/// A fixed-memory log-linear latency histogram, in the spirit of HDR histograms
+///
+/// Values are recorded with nanosecond precision below 32ns, and with a relative error of
+/// about 3% above that, up to a bit over 2 hours. Percentiles are reported as the upper bound
+/// of the bucket they fall into, clamped to the exact minimum and maximum recorded.
+///
+/// ```rust
+/// # use async_instrumenter::Histogram;
+/// # use std::time::Duration;
+/// let mut histogram = Histogram::new();
+///
+/// for ms in 1..=100 {
+///     histogram.record(Duration::from_millis(ms));
+/// }
+///
+/// let p99 = histogram.percentile(99.0);
+/// assert!(p99.abs_diff(Duration::from_millis(99)) < Duration::from_millis(3));
+///
+/// // histograms from different threads can be combined
+/// let mut other = Histogram::new();
+/// other.record(Duration::from_secs(1));
+/// histogram.merge(&other);
+///
+/// assert_eq!(histogram.count(), 101);
+/// assert_eq!(histogram.max(), Duration::from_secs(1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    counts: Box<[u64]>,
+    count: u64,
+    sum: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; BUCKETS].into_boxed_slice(),
+            count: 0,
+            sum: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+        }
+    }
+
+    pub fn record(&mut self, value: Duration) {
+        self.counts[index(value)] += 1;
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Add all values recorded in `other` to this histogram
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Forget all recorded values
+    pub fn reset(&mut self) {
+        self.counts.fill(0);
+        self.count = 0;
+        self.sum = Duration::ZERO;
+        self.min = Duration::MAX;
+        self.max = Duration::ZERO;
+    }
+
+    /// How many values were recorded
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The total of all recorded values
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// The smallest recorded value, zero if nothing was recorded
+    pub fn min(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.min
+        }
+    }
+
+    /// The largest recorded value
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// The average of all recorded values, zero if nothing was recorded
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.sum.div_f64(self.count as f64)
+        }
+    }
+
+    /// The value below which `percentile` percent of the recorded values fall, zero if nothing was recorded
+    ///
+    /// `percentile` is clamped to `0.0..=100.0`, e.g. `99.9` for the p999.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+
+        let rank =
+            ((percentile.clamp(0.0, 100.0) / 100.0 * self.count as f64).ceil() as u64).max(1);
+
+        let mut seen = 0;
+        for (i, &count) in self.counts.iter().enumerate() {
+            seen += count;
+
+            if seen >= rank {
+                return upper_bound(i).clamp(self.min, self.max);
+            }
+        }
+
+        self.max
+    }
+
+    /// The number of recorded values up to and including `value`, within the precision of the buckets
+    pub fn count_up_to(&self, value: Duration) -> u64 {
+        self.counts[..=index(value)].iter().sum()
+    }
+
+    /// The non-empty buckets
+    pub fn buckets(&self) -> impl Iterator<Item = Bucket> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(i, &count)| Bucket {
+                upper_bound: upper_bound(i),
+                count,
+            })
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A histogram bucket, counting the values up to and including its upper bound and above the previous bucket's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Bucket {
+    pub upper_bound: Duration,
+    pub count: u64,
+}
+
+fn index(value: Duration) -> usize {
+    let nanos = u64::try_from(value.as_nanos()).unwrap_or(u64::MAX);
+
+    if nanos < SUB_BUCKETS as u64 {
+        return nanos as usize;
+    }
+
+    let magnitude = u64::BITS - 1 - nanos.leading_zeros();
+    if magnitude > MAX_MAGNITUDE {
+        return BUCKETS - 1;
+    }
+
+    let shift = magnitude - SUB_BITS;
+    let sub_bucket = (nanos >> shift) as usize - SUB_BUCKETS;
+
+    (shift as usize + 1) * SUB_BUCKETS + sub_bucket
+}
+
+fn upper_bound(index: usize) -> Duration {
+    if index < SUB_BUCKETS {
+        return Duration::from_nanos(index as u64);
+    }
+
+    if index == BUCKETS - 1 {
+        return Duration::MAX;
+    }
+
+    let shift = (index / SUB_BUCKETS - 1) as u32;
+    let lower = ((SUB_BUCKETS + index % SUB_BUCKETS) as u64) << shift;
+
+    Duration::from_nanos(lower + (1 << shift) - 1)
+}
@@ -0,0 +1,303 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    panic::Location,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+use crate::emit::Labels;
+
+static GLOBAL: InFlight = InFlight::new();
+
+/// The instrumented futures which are currently pending
+///
+/// Tracking is off by default, as it costs a lock around every poll. Once [enabled](InFlight::enable),
+/// every [`InstrumentFuture`](crate::InstrumentFuture) polled for the first time is added here until it
+/// completes or is dropped. Times are always measured with [`Instant`], regardless of the future's clock.
+///
+/// A [`Watchdog`](crate::Watchdog) enables tracking and reports the futures which have been pending for too long.
+///
+/// ```rust
+/// # use async_instrumenter::{InFlight, InstrumentFuture};
+/// # use std::{future::{pending, Future}, pin::pin, task::{Context, Waker}};
+/// InFlight::global().enable();
+///
+/// let mut fut = pin!(InstrumentFuture::new(pending::<()>()).name("stuck"));
+/// assert!(fut.as_mut().poll(&mut Context::from_waker(Waker::noop())).is_pending());
+///
+/// let stuck = InFlight::global().snapshot();
+/// assert_eq!(stuck[0].name, Some("stuck"));
+/// assert_eq!(stuck[0].polls, 1);
+/// ```
+#[derive(Debug)]
+pub struct InFlight {
+    enabled: AtomicBool,
+    next_id: AtomicU64,
+    futures: Mutex<BTreeMap<u64, Arc<Entry>>>,
+}
+
+impl InFlight {
+    const fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            next_id: AtomicU64::new(0),
+            futures: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// The registry every [`InstrumentFuture`](crate::InstrumentFuture) is tracked in
+    pub fn global() -> &'static InFlight {
+        &GLOBAL
+    }
+
+    /// Start tracking futures on their first poll
+    ///
+    /// Futures which were already polled before don't get tracked.
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    /// Stop tracking futures on their first poll
+    ///
+    /// Futures which are already tracked stay so until they complete or are dropped.
+    pub fn disable(&self) {
+        self.enabled.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Every tracked future, the oldest first
+    pub fn snapshot(&self) -> Vec<InFlightFuture> {
+        let now = Instant::now();
+
+        let futures = self.futures.lock().unwrap_or_else(PoisonError::into_inner);
+        // ids are handed out in order, so the oldest come first already
+        futures.values().map(|entry| entry.summary(now)).collect()
+    }
+
+    /// A table of every tracked future, the oldest first
+    ///
+    /// ```rust
+    /// # use async_instrumenter::{InFlight, InstrumentFuture};
+    /// # use std::{future::{pending, Future}, pin::pin, task::{Context, Waker}};
+    /// InFlight::global().enable();
+    ///
+    /// let mut fut = pin!(InstrumentFuture::new(pending::<()>()).name("stuck"));
+    /// let _ = fut.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+    ///
+    /// let dump = InFlight::global().dump();
+    /// assert!(dump.lines().nth(2).unwrap().trim_start().starts_with("stuck"));
+    /// println!("{dump}");
+    /// ```
+    pub fn dump(&self) -> String {
+        let futures = self.snapshot();
+
+        let sites = futures
+            .iter()
+            .map(|future| {
+                let mut name = future.name.unwrap_or("-").to_owned();
+                if !future.labels.is_empty() {
+                    let _ = write!(name, "{}", Labels(&future.labels));
+                }
+
+                let callsite = format!("{}:{}", future.location.file(), future.location.line());
+                (name, callsite)
+            })
+            .collect::<Vec<_>>();
+
+        let name_width = sites
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max(4);
+        let callsite_width = sites
+            .iter()
+            .map(|(_, callsite)| callsite.len())
+            .max()
+            .unwrap_or(0)
+            .max(8);
+
+        let mut dump = format!(
+            "{} instrumented futures in flight\n  {:<name_width$} {:<callsite_width$} {:>10} {:>10} {:>8}  thread",
+            futures.len(),
+            "name",
+            "callsite",
+            "age",
+            "last poll",
+            "polls"
+        );
+
+        for (future, (name, callsite)) in futures.iter().zip(&sites) {
+            let _ = write!(
+                dump,
+                "\n  {:<name_width$} {:<callsite_width$} {:>10.2?} {:>10.2?} {:>8}  {}",
+                name,
+                callsite,
+                future.age,
+                future.since_last_poll,
+                future.polls,
+                future.last_thread
+            );
+        }
+
+        dump
+    }
+
+    /// Log a [`dump`](InFlight::dump) at `level` whenever the process receives `SIGUSR1`
+    ///
+    /// This also [enables](InFlight::enable) tracking. The signal is handled on a background thread
+    /// for the rest of the process, which is only started by the first successful call, so calling
+    /// this again does nothing. The dump is logged with the `async_instrumenter` target.
+    ///
+    /// Only available on unix with the `signal-hook` feature.
+    #[cfg(all(unix, feature = "signal-hook"))]
+    pub fn dump_on_sigusr1(&'static self, level: log::Level) -> std::io::Result<()> {
+        use signal_hook::{consts::SIGUSR1, iterator::Signals};
+
+        static LISTENING: Mutex<bool> = Mutex::new(false);
+
+        let mut listening = LISTENING.lock().unwrap_or_else(PoisonError::into_inner);
+        if *listening {
+            return Ok(());
+        }
+
+        let mut signals = Signals::new([SIGUSR1])?;
+
+        thread::Builder::new()
+            .name("async-instrumenter-dump".to_owned())
+            .spawn(move || {
+                for _ in signals.forever() {
+                    crate::emit::report(level, &self.dump());
+                }
+            })?;
+
+        *listening = true;
+        self.enable();
+
+        Ok(())
+    }
+
+    /// Start tracking a future, if tracking is enabled
+    pub(crate) fn track(
+        &'static self,
+        name: Option<&'static str>,
+        location: &'static Location<'static>,
+        labels: &[(&'static str, String)],
+    ) -> Option<Tracked> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let now = Instant::now();
+        let entry = Arc::new(Entry {
+            name,
+            labels: labels.to_vec(),
+            location,
+            first_poll: now,
+            polls: AtomicU64::new(0),
+            last_poll: Mutex::new((now, thread::current())),
+        });
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.futures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id, entry.clone());
+
+        Some(Tracked {
+            registry: self,
+            id,
+            entry,
+        })
+    }
+}
+
+/// A future which is currently pending, see [`InFlight`]
+#[derive(Debug, Clone)]
+pub struct InFlightFuture {
+    /// The name given with [`InstrumentFuture::name`](crate::InstrumentFuture::name), if any
+    pub name: Option<&'static str>,
+    /// The labels attached with [`InstrumentFuture::label`](crate::InstrumentFuture::label)
+    pub labels: Vec<(&'static str, String)>,
+    /// Where the [`InstrumentFuture`](crate::InstrumentFuture) was created
+    pub location: &'static Location<'static>,
+    /// Time since the future was first polled
+    pub age: Duration,
+    /// Time since the future's last poll returned
+    pub since_last_poll: Duration,
+    /// How many times the future was polled
+    pub polls: u64,
+    /// The thread which polled the future last, by its name or its id if it has none
+    pub last_thread: String,
+}
+
+#[derive(Debug)]
+struct Entry {
+    name: Option<&'static str>,
+    labels: Vec<(&'static str, String)>,
+    location: &'static Location<'static>,
+    first_poll: Instant,
+    polls: AtomicU64,
+    last_poll: Mutex<(Instant, Thread)>,
+}
+
+impl Entry {
+    fn summary(&self, now: Instant) -> InFlightFuture {
+        let (last_poll, last_thread) = self
+            .last_poll
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        InFlightFuture {
+            name: self.name,
+            labels: self.labels.clone(),
+            location: self.location,
+            age: now.saturating_duration_since(self.first_poll),
+            since_last_poll: now.saturating_duration_since(last_poll),
+            polls: self.polls.load(Ordering::Relaxed),
+            last_thread: match last_thread.name() {
+                Some(name) => name.to_owned(),
+                None => format!("{:?}", last_thread.id()),
+            },
+        }
+    }
+}
+
+/// A tracked future's entry in the [`InFlight`] registry, removed again when dropped
+#[derive(Debug)]
+pub(crate) struct Tracked {
+    registry: &'static InFlight,
+    id: u64,
+    entry: Arc<Entry>,
+}
+
+impl Tracked {
+    /// Note that a poll of the future just returned
+    pub(crate) fn polled(&self) {
+        self.entry.polls.fetch_add(1, Ordering::Relaxed);
+        *self
+            .entry
+            .last_poll
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = (Instant::now(), thread::current());
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.registry
+            .futures
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.id);
+    }
+}
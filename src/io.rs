@@ -0,0 +1,431 @@
+use std::{
+    fmt::{self, Debug},
+    io,
+    panic::Location,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use log::Level;
+use pin_project::{pin_project, pinned_drop};
+
+use crate::{emit, Clock};
+
+/// The timing of an [`InstrumentIo`]
+///
+/// Passed to the callback registered with [`InstrumentIo::on_report`]
+#[derive(Debug, Clone)]
+pub struct InstrumentIoResult {
+    /// The name given with [`InstrumentIo::name`], if any
+    pub name: Option<&'static str>,
+    /// Wall-clock time from the first read or write until the report
+    pub elapsed: Duration,
+    /// How many bytes were read
+    pub bytes_read: u64,
+    /// How many bytes were written
+    pub bytes_written: u64,
+    /// How many times the I/O object was polled
+    pub polls: u64,
+    /// How many of those polls returned `Poll::Pending`
+    pub pending: u64,
+    /// Cumulative time spent inside the I/O object's `poll_*` methods
+    pub busy: Duration,
+    /// Cumulative time spent waiting for the I/O object to become ready again after it returned `Poll::Pending`
+    pub blocked: Duration,
+}
+
+impl InstrumentIoResult {
+    /// Bytes read per second over the elapsed time
+    pub fn read_throughput(&self) -> f64 {
+        per_second(self.bytes_read, self.elapsed)
+    }
+
+    /// Bytes written per second over the elapsed time
+    pub fn write_throughput(&self) -> f64 {
+        per_second(self.bytes_written, self.elapsed)
+    }
+
+    /// Bytes read and written per second over the elapsed time
+    pub fn throughput(&self) -> f64 {
+        per_second(self.bytes_read + self.bytes_written, self.elapsed)
+    }
+}
+
+fn per_second(bytes: u64, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        bytes as f64 / elapsed.as_secs_f64()
+    }
+}
+
+type Callback = Box<dyn FnOnce(InstrumentIoResult) + Send + Sync>;
+
+/// Wraps an I/O object and measures how much data goes through it, and how long that takes
+///
+/// Implements `AsyncRead`/`AsyncWrite` from `tokio` (with the `tokio` feature) and from `futures-io`
+/// (with the `futures-io` feature) whenever the wrapped object does. The bytes transferred, the number
+/// of pending returns, and the time spent busy inside the `poll_*` methods versus blocked waiting for
+/// the object to become ready again are reported once the object is shut down (or closed), or dropped.
+///
+/// ```rust
+/// # use async_instrumenter::InstrumentIo;
+/// # fn connect() -> std::io::Cursor<Vec<u8>> { Default::default() }
+/// let conn = InstrumentIo::new(connect()).on_report(|res| {
+///     println!(
+///         "read {} bytes at {:.0} B/s, blocked for {:?}",
+///         res.bytes_read,
+///         res.read_throughput(),
+///         res.blocked
+///     );
+/// });
+/// ```
+#[pin_project(PinnedDrop)]
+pub struct InstrumentIo<T, C: Clock = Instant> {
+    #[pin]
+    io: T,
+    stats: IoStats<C>,
+    on_report: Option<Callback>,
+}
+
+impl<T> InstrumentIo<T> {
+    pub fn new(io: T) -> Self {
+        Self::with_clock(io)
+    }
+}
+
+impl<T, C: Clock> InstrumentIo<T, C> {
+    /// Create an instrumenting I/O object which measures time with the clock `C`
+    pub fn with_clock(io: T) -> Self {
+        Self {
+            io,
+            stats: IoStats::new(),
+            on_report: None,
+        }
+    }
+
+    /// Give the I/O object a name to identify it by in reports
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.stats.name = Some(name);
+        self
+    }
+
+    /// Call `callback` once the I/O object is shut down or dropped
+    pub fn on_report(
+        mut self,
+        callback: impl FnOnce(InstrumentIoResult) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_report = Some(Box::new(callback));
+        self
+    }
+
+    /// Log the report at `level` once the I/O object is shut down or dropped
+    ///
+    /// Like [`InstrumentExt::log_elapsed`](crate::InstrumentExt::log_elapsed), the caller's file and line are
+    /// part of the message, which is logged with the `async_instrumenter` target.
+    #[track_caller]
+    pub fn log_report(self, level: Level) -> Self {
+        let location = Location::caller();
+        self.on_report(move |res| emit::io(level, location, &res))
+    }
+
+    /// The wrapped I/O object
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// The wrapped I/O object
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    /// The timing so far, without ending the measurement
+    pub fn stats(&self) -> InstrumentIoResult {
+        self.stats.result(C::now())
+    }
+}
+
+impl<T: Debug, C: Clock + Debug> Debug for InstrumentIo<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstrumentIo")
+            .field("io", &self.io)
+            .field("stats", &self.stats)
+            .field("on_report", &self.on_report.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+#[pinned_drop]
+impl<T, C: Clock> PinnedDrop for InstrumentIo<T, C> {
+    fn drop(self: Pin<&mut Self>) {
+        let this = self.project();
+
+        if let Some(callback) = this.on_report.take() {
+            callback(this.stats.result(C::now()));
+        }
+    }
+}
+
+/// Which direction an I/O call transfers data in, tracked separately to tell when each was blocked
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    Read,
+    Write,
+}
+
+/// Bookkeeping of the calls into an [`InstrumentIo`]
+#[derive(Debug)]
+struct IoStats<C> {
+    name: Option<&'static str>,
+    first_poll: Option<C>,
+    bytes_read: u64,
+    bytes_written: u64,
+    polls: u64,
+    pending: u64,
+    busy: Duration,
+    blocked: Duration,
+    read_pending_since: Option<C>,
+    write_pending_since: Option<C>,
+}
+
+impl<C: Clock> IoStats<C> {
+    fn new() -> Self {
+        Self {
+            name: None,
+            first_poll: None,
+            bytes_read: 0,
+            bytes_written: 0,
+            polls: 0,
+            pending: 0,
+            busy: Duration::ZERO,
+            blocked: Duration::ZERO,
+            read_pending_since: None,
+            write_pending_since: None,
+        }
+    }
+
+    /// Time a single call into the I/O object
+    fn track<R>(&mut self, direction: Direction, poll: impl FnOnce() -> Poll<R>) -> Poll<R> {
+        let start = C::now();
+        self.first_poll.get_or_insert(start);
+
+        let pending_since = match direction {
+            Direction::Read => &mut self.read_pending_since,
+            Direction::Write => &mut self.write_pending_since,
+        };
+
+        if let Some(pending_since) = pending_since.take() {
+            self.blocked += start.duration_since(pending_since);
+        }
+
+        let res = poll();
+
+        let end = C::now();
+        self.busy += end.duration_since(start);
+        self.polls += 1;
+
+        if res.is_pending() {
+            self.pending += 1;
+            *pending_since = Some(end);
+        }
+
+        res
+    }
+
+    fn result(&self, end: C) -> InstrumentIoResult {
+        InstrumentIoResult {
+            name: self.name,
+            elapsed: self
+                .first_poll
+                .map_or(Duration::ZERO, |first_poll| end.duration_since(first_poll)),
+            bytes_read: self.bytes_read,
+            bytes_written: self.bytes_written,
+            polls: self.polls,
+            pending: self.pending,
+            busy: self.busy,
+            blocked: self.blocked,
+        }
+    }
+}
+
+/// Report once a shutdown completed
+fn shut_down<C: Clock>(
+    stats: &IoStats<C>,
+    on_report: &mut Option<Callback>,
+    res: &Poll<io::Result<()>>,
+) {
+    if let Poll::Ready(Ok(())) = res {
+        if let Some(callback) = on_report.take() {
+            callback(stats.result(C::now()));
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T: tokio::io::AsyncRead, C: Clock> tokio::io::AsyncRead for InstrumentIo<T, C> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+
+        let before = buf.filled().len();
+        let res = this
+            .stats
+            .track(Direction::Read, || this.io.poll_read(cx, buf));
+        this.stats.bytes_read += (buf.filled().len() - before) as u64;
+
+        res
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T: tokio::io::AsyncWrite, C: Clock> tokio::io::AsyncWrite for InstrumentIo<T, C> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+
+        let res = this
+            .stats
+            .track(Direction::Write, || this.io.poll_write(cx, buf));
+        if let Poll::Ready(Ok(n)) = res {
+            this.stats.bytes_written += n as u64;
+        }
+
+        res
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+
+        let res = this
+            .stats
+            .track(Direction::Write, || this.io.poll_write_vectored(cx, bufs));
+        if let Poll::Ready(Ok(n)) = res {
+            this.stats.bytes_written += n as u64;
+        }
+
+        res
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.project();
+        this.stats
+            .track(Direction::Write, || this.io.poll_flush(cx))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.project();
+
+        let res = this
+            .stats
+            .track(Direction::Write, || this.io.poll_shutdown(cx));
+        shut_down(this.stats, this.on_report, &res);
+
+        res
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<T: futures_io::AsyncRead, C: Clock> futures_io::AsyncRead for InstrumentIo<T, C> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+
+        let res = this
+            .stats
+            .track(Direction::Read, || this.io.poll_read(cx, buf));
+        if let Poll::Ready(Ok(n)) = res {
+            this.stats.bytes_read += n as u64;
+        }
+
+        res
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [io::IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+
+        let res = this
+            .stats
+            .track(Direction::Read, || this.io.poll_read_vectored(cx, bufs));
+        if let Poll::Ready(Ok(n)) = res {
+            this.stats.bytes_read += n as u64;
+        }
+
+        res
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<T: futures_io::AsyncWrite, C: Clock> futures_io::AsyncWrite for InstrumentIo<T, C> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+
+        let res = this
+            .stats
+            .track(Direction::Write, || this.io.poll_write(cx, buf));
+        if let Poll::Ready(Ok(n)) = res {
+            this.stats.bytes_written += n as u64;
+        }
+
+        res
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+
+        let res = this
+            .stats
+            .track(Direction::Write, || this.io.poll_write_vectored(cx, bufs));
+        if let Poll::Ready(Ok(n)) = res {
+            this.stats.bytes_written += n as u64;
+        }
+
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.project();
+        this.stats
+            .track(Direction::Write, || this.io.poll_flush(cx))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.project();
+
+        let res = this
+            .stats
+            .track(Direction::Write, || this.io.poll_close(cx));
+        shut_down(this.stats, this.on_report, &res);
+
+        res
+    }
+}
@@ -1,25 +1,230 @@
+/// Instrument a whole `async fn`, logging how long each call took to execute
+///
+/// The function body gets wrapped in [`instrument!`], named after the function's path. The same options as
+/// [`instrument!`] can be passed as `key = value` pairs: `target`, `level`, `threshold`, `escalate`, `labels`,
+/// `message` (which must use `elapsed`, like the custom log message of [`instrument!`]) and `name` to
+/// override the function's path. Labels can use the function's arguments.
+///
+/// The expanded code refers to `::async_instrumenter`, so when the crate is only reachable through a re-export
+/// or under another name, its path has to be passed as `crate = <path>`.
+///
+/// ```rust
+/// # use async_instrumenter::instrument_async;
+/// # use log::Level;
+/// # use std::time::Duration;
+/// #[instrument_async]
+/// async fn fetch_user(id: u32) -> Result<u32, String> {
+///     Ok(id)
+/// }
+///
+/// #[instrument_async(labels = [tenant = tenant])]
+/// async fn fetch_tenant(tenant: u32) -> u32 {
+///     tenant
+/// }
+///
+/// #[instrument_async(
+///     level = Level::Info,
+///     threshold = Duration::from_millis(50),
+///     escalate = (Level::Warn, Duration::from_millis(500)),
+///     message = "fetching users took {elapsed:?}"
+/// )]
+/// async fn fetch_users() -> Result<Vec<u32>, String> {
+///     Ok(vec![fetch_user(0).await?, fetch_user(1).await?])
+/// }
+///
+/// extern crate async_instrumenter as instrumenter;
+///
+/// #[instrument_async(crate = instrumenter)]
+/// async fn fetch_nothing() {}
+/// ```
+///
+/// Only available with the `attributes` feature.
+#[cfg(feature = "attributes")]
+pub use async_instrumenter_macros::instrument_async;
 pub use log;
+#[cfg(feature = "tracing")]
+pub use tracing;
+
+mod background;
+mod clock;
+mod emit;
+mod ext;
+mod histogram;
+mod in_flight;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod io;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+mod panic;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+mod registry;
+mod reporter;
+#[cfg(feature = "stream")]
+mod stream;
+mod wake;
+mod watchdog;
+
+pub use background::BackgroundHandle;
+pub use clock::{Clock, MockClock};
+pub use ext::{InstrumentExt, LogElapsed};
+pub use histogram::{Bucket, Histogram};
+pub use in_flight::{InFlight, InFlightFuture};
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use io::{InstrumentIo, InstrumentIoResult};
+pub use panic::PanicLocation;
+pub use registry::{Registry, RegistrySnapshot, SiteSummary};
+pub use reporter::Reporter;
+#[cfg(feature = "stream")]
+pub use stream::{InstrumentStream, InstrumentStreamResult};
+pub use wake::WakeStats;
+pub use watchdog::Watchdog;
 
 use std::{
+    backtrace::Backtrace,
+    fmt::{self, Debug, Display},
     future::Future,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe, Location},
     pin::Pin,
-    task::{Context, Poll},
+    task::{Context, Poll, Waker},
     time::{Duration, Instant},
 };
 
-use pin_project::pin_project;
+use emit::Site;
+use pin_project::{pin_project, pinned_drop};
 
-// The result of a finished [`InstrumentFuture`]
+/// The result of a finished [`InstrumentFuture`]
 #[derive(Debug)]
 pub struct InstrumentFutureResult<R> {
+    /// The output of the wrapped future
     pub result: R,
+    /// The name given with [`InstrumentFuture::name`], if any
+    pub name: Option<&'static str>,
+    /// The labels attached with [`InstrumentFuture::label`], in the order they were attached
+    pub labels: Vec<(&'static str, String)>,
+    /// Where the [`InstrumentFuture`] was created
+    pub location: &'static Location<'static>,
+    /// Wall-clock time from the first poll until the future completed (the execution time)
+    pub elapsed: Duration,
+    /// Time from creating the [`InstrumentFuture`] until it was first polled (the scheduling latency)
+    ///
+    /// Only measured when [`InstrumentFuture::time_from_creation`] was used, otherwise `None`
+    pub time_to_first_poll: Option<Duration>,
+    /// Cumulative time spent inside the wrapped future's `poll`
+    pub busy: Duration,
+    /// Time spent pending between polls, i.e. `elapsed - busy`
+    pub idle: Duration,
+    /// How many times the wrapped future was polled
+    pub polls: u64,
+    /// Duration of the shortest single poll
+    pub min_poll: Duration,
+    /// Duration of the longest single poll
+    pub max_poll: Duration,
+    /// Average duration of a single poll, i.e. `busy / polls`
+    pub mean_poll: Duration,
+    /// CPU time consumed by the polling threads while inside the wrapped future's `poll`
+    ///
+    /// Only measured when [`InstrumentFuture::measure_cpu_time`] was used and the platform
+    /// supports it, otherwise `None`
+    pub cpu_time: Option<Duration>,
+    /// How the wrapped future was woken
+    ///
+    /// Only tracked when [`InstrumentFuture::track_wakes`] was used, otherwise `None`
+    pub wakes: Option<WakeStats>,
+    /// The threshold configured with [`InstrumentFuture::threshold`], if any
+    pub threshold: Option<Duration>,
+}
+
+impl<R> InstrumentFutureResult<R> {
+    /// Whether the future took longer than its [`threshold`](InstrumentFutureResult::threshold)
+    ///
+    /// Always `true` if no threshold was configured
+    pub fn is_slow(&self) -> bool {
+        self.threshold
+            .is_none_or(|threshold| self.elapsed > threshold)
+    }
+}
+
+/// Timing of an [`InstrumentFuture`] which was dropped before it completed
+///
+/// Passed to the callback registered with [`InstrumentFuture::on_cancel`]
+#[derive(Debug, Clone)]
+pub struct InstrumentFutureCancelled {
+    /// The name given with [`InstrumentFuture::name`], if any
+    pub name: Option<&'static str>,
+    /// The labels attached with [`InstrumentFuture::label`]
+    pub labels: Vec<(&'static str, String)>,
+    /// Where the [`InstrumentFuture`] was created
+    pub location: &'static Location<'static>,
+    /// Wall-clock time from the first poll until the future was dropped
     pub elapsed: Duration,
+    /// Time from creating the [`InstrumentFuture`] until it was first polled
+    ///
+    /// Only measured when [`InstrumentFuture::time_from_creation`] was used and the future
+    /// was polled at least once, otherwise `None`
+    pub time_to_first_poll: Option<Duration>,
+    /// Cumulative time spent inside the wrapped future's `poll`
+    pub busy: Duration,
+    /// Time spent pending between polls, i.e. `elapsed - busy`
+    pub idle: Duration,
+    /// How many times the wrapped future was polled before being dropped
+    pub polls: u64,
+    /// Duration of the shortest single poll
+    pub min_poll: Duration,
+    /// Duration of the longest single poll
+    pub max_poll: Duration,
+    /// Average duration of a single poll, i.e. `busy / polls`
+    pub mean_poll: Duration,
+    /// CPU time consumed while inside the wrapped future's `poll`, see [`InstrumentFutureResult::cpu_time`]
+    pub cpu_time: Option<Duration>,
+    /// How the wrapped future was woken, see [`InstrumentFutureResult::wakes`]
+    pub wakes: Option<WakeStats>,
 }
 
+/// Timing of an [`InstrumentFuture`] whose wrapped future panicked while being polled
+///
+/// Passed to the callback registered with [`InstrumentFuture::on_panic`]
+#[derive(Debug, Clone)]
+pub struct InstrumentFuturePanicked {
+    /// The name given with [`InstrumentFuture::name`], if any
+    pub name: Option<&'static str>,
+    /// The labels attached with [`InstrumentFuture::label`]
+    pub labels: Vec<(&'static str, String)>,
+    /// Where the [`InstrumentFuture`] was created
+    pub location: &'static Location<'static>,
+    /// Wall-clock time from the first poll until the future panicked
+    pub elapsed: Duration,
+    /// Cumulative time spent inside the wrapped future's `poll`, including the panicking poll
+    pub busy: Duration,
+    /// Time spent pending between polls, i.e. `elapsed - busy`
+    pub idle: Duration,
+    /// How many times the wrapped future was polled, including the panicking poll
+    pub polls: u64,
+    /// Where the panic was raised
+    ///
+    /// This is recorded by a panic hook installed alongside the previous one, so it's `None`
+    /// if the hook was replaced since
+    pub panic_location: Option<PanicLocation>,
+    /// The panic message, if the panic payload was a string
+    pub message: Option<String>,
+}
+
+type CancelCallback = Box<dyn FnOnce(InstrumentFutureCancelled) + Send + Sync>;
+type PanicCallback = Box<dyn FnOnce(InstrumentFuturePanicked) + Send + Sync>;
+
 /// Wraps a future and determines exactly how long it took to execute
 ///
+/// Every individual call to the inner future's `poll` is timed, so besides the total
+/// elapsed time the result also tells apart time spent working inside `poll` (`busy`)
+/// from time spent waiting to be woken up again (`idle`).
+///
 /// ```rust
-/// let my_fut: impl Future<Output = ()> = foobar();
+/// # use async_instrumenter::InstrumentFuture;
+/// # async fn foobar() {}
+/// # async fn run() {
+/// let my_fut = foobar();
 /// let res = InstrumentFuture::new(my_fut).await;
 ///
 /// // get the result of `my_fut`
@@ -27,42 +232,649 @@ pub struct InstrumentFutureResult<R> {
 ///
 /// // print the elapsed time of `my_fut`
 /// println!("my_fut took {:?}", res.elapsed);
+///
+/// // and how that time was split between polling and waiting
+/// println!("busy {:?}, idle {:?}", res.busy, res.idle);
+///
+/// // a high poll count usually hints at spurious wakeups
+/// println!("polled {} times, longest poll {:?}", res.polls, res.max_poll);
+/// # }
 /// ```
-#[derive(Debug)]
-#[pin_project]
-pub struct InstrumentFuture<F: Future> {
+///
+/// Time is measured with [`Instant`] by default. Any other [`Clock`] can be used through
+/// [`InstrumentFuture::with_clock`], e.g. [`MockClock`] to make measurements deterministic in tests.
+///
+/// With the `opentelemetry` feature enabled, every instrumented future is also exported as a span through
+/// the global tracer provider, see [`InstrumentFuture::otel_parent`].
+#[pin_project(PinnedDrop)]
+pub struct InstrumentFuture<F: Future, C: Clock = Instant> {
     #[pin]
     future: F,
-    timer: Option<Instant>,
+    stats: PollStats<C>,
+    on_cancel: Option<CancelCallback>,
+    on_panic: Option<PanicCallback>,
+    #[cfg(feature = "opentelemetry")]
+    span: otel::OtelSpan,
+    done: bool,
 }
 
 impl<F: Future> InstrumentFuture<F> {
+    #[track_caller]
     pub fn new(future: F) -> Self {
+        Self::with_clock(future)
+    }
+}
+
+impl<F: Future, C: Clock> InstrumentFuture<F, C> {
+    /// Create an instrumenting future which measures time with the clock `C`
+    ///
+    /// ```rust
+    /// # use async_instrumenter::{InstrumentFuture, MockClock};
+    /// # async fn foobar() {}
+    /// let fut = InstrumentFuture::<_, MockClock>::with_clock(foobar());
+    /// ```
+    #[track_caller]
+    pub fn with_clock(future: F) -> Self {
         Self {
             future,
-            timer: None,
+            stats: PollStats::new(Location::caller()),
+            on_cancel: None,
+            on_panic: None,
+            #[cfg(feature = "opentelemetry")]
+            span: otel::OtelSpan::new(),
+            done: false,
         }
     }
+
+    /// Give the future a name to identify it by in reports
+    ///
+    /// ```rust
+    /// # use async_instrumenter::InstrumentFuture;
+    /// # async fn fetch_user() {}
+    /// # async fn run() {
+    /// let res = InstrumentFuture::new(fetch_user()).name("fetch_user").await;
+    /// assert_eq!(res.name, Some("fetch_user"));
+    /// # }
+    /// ```
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.stats.name = Some(name);
+        self
+    }
+
+    /// Attach a `key=value` label to tell apart the futures of the same site in reports
+    ///
+    /// Labels are carried over to [`InstrumentFutureResult::labels`], shown next to the name in log messages,
+    /// and exported along with the timings, e.g. as separate sites in the [`Registry`] or as metric labels.
+    /// Every distinct set of values makes a separate site, so they should only take a few distinct values.
+    ///
+    /// Keys have to be valid Prometheus label names, matching `[A-Za-z_][A-Za-z0-9_]*` and not starting with
+    /// `__`, and can't be one of the keys added on export: `site`, `le`, `name`, `outcome`, `file` and `line`.
+    /// The macros reject other keys at compile time, and [`Registry::record_result`] panics on them.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::InstrumentFuture;
+    /// # async fn fetch_user() {}
+    /// # async fn run() {
+    /// let res = InstrumentFuture::new(fetch_user())
+    ///     .name("fetch_user")
+    ///     .label("endpoint", "/users")
+    ///     .label("tenant", 42)
+    ///     .await;
+    ///
+    /// assert_eq!(res.labels, [("endpoint", "/users".to_owned()), ("tenant", "42".to_owned())]);
+    /// # }
+    /// ```
+    pub fn label(mut self, key: &'static str, value: impl Display) -> Self {
+        self.stats.labels.push((key, value.to_string()));
+        self
+    }
+
+    /// Attach several labels at once, see [`InstrumentFuture::label`]
+    pub fn labels<V: Display>(
+        mut self,
+        labels: impl IntoIterator<Item = (&'static str, V)>,
+    ) -> Self {
+        for (key, value) in labels {
+            self.stats.labels.push((key, value.to_string()));
+        }
+        self
+    }
+
+    /// Start the clock now instead of on the first poll
+    ///
+    /// The time until the executor first polls the future is then reported separately
+    /// as [`InstrumentFutureResult::time_to_first_poll`], while `elapsed` still only covers
+    /// the execution time.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::InstrumentFuture;
+    /// # async fn foobar() {}
+    /// # async fn run() {
+    /// let res = InstrumentFuture::new(foobar()).time_from_creation().await;
+    ///
+    /// println!(
+    ///     "waited {:?} to be scheduled, then ran for {:?}",
+    ///     res.time_to_first_poll.unwrap(),
+    ///     res.elapsed
+    /// );
+    /// # }
+    /// ```
+    pub fn time_from_creation(mut self) -> Self {
+        self.stats.created = Some(C::now());
+        self
+    }
+
+    /// Also measure the CPU time spent inside the wrapped future's `poll`
+    ///
+    /// The polling thread's CPU clock is sampled before and after every poll, so unlike `busy`
+    /// this doesn't count time the OS spent running other threads, and stays correct when the
+    /// future migrates between worker threads. The result is reported as
+    /// [`InstrumentFutureResult::cpu_time`].
+    ///
+    /// This is currently only supported on unix platforms, elsewhere `cpu_time` stays `None`.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::InstrumentFuture;
+    /// # async fn foobar() {}
+    /// # async fn run() {
+    /// let res = InstrumentFuture::new(foobar()).measure_cpu_time().await;
+    ///
+    /// if let Some(cpu_time) = res.cpu_time {
+    ///     println!("used {cpu_time:?} of cpu time out of {:?} busy", res.busy);
+    /// }
+    /// # }
+    /// ```
+    pub fn measure_cpu_time(mut self) -> Self {
+        self.stats.cpu_time = clock::thread_cpu_time().map(|_| Duration::ZERO);
+        self
+    }
+
+    /// Call `callback` if this future is dropped before it completed
+    ///
+    /// This happens when e.g. a `select!` or timeout gives up on the future. The callback
+    /// receives the timing collected up until the moment the future was dropped.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::InstrumentFuture;
+    /// # use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+    /// # async fn foobar() {}
+    /// let was_cancelled = Arc::new(AtomicBool::new(false));
+    ///
+    /// let fut = InstrumentFuture::new(foobar()).on_cancel({
+    ///     let was_cancelled = was_cancelled.clone();
+    ///     move |cancelled| {
+    ///         println!("cancelled after {:?} ({} polls)", cancelled.elapsed, cancelled.polls);
+    ///         was_cancelled.store(true, Ordering::Relaxed);
+    ///     }
+    /// });
+    ///
+    /// // never completed, so the callback runs
+    /// drop(fut);
+    /// assert!(was_cancelled.load(Ordering::Relaxed));
+    /// ```
+    pub fn on_cancel(
+        mut self,
+        callback: impl FnOnce(InstrumentFutureCancelled) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_cancel = Some(Box::new(callback));
+        self
+    }
+
+    /// Only consider the future slow, and therefore worth reporting, if it took longer than `threshold`
+    ///
+    /// The threshold is carried over to [`InstrumentFutureResult::threshold`] so callers can check
+    /// [`InstrumentFutureResult::is_slow`] before reporting, and the [`on_cancel`](InstrumentFuture::on_cancel)
+    /// callback is skipped for futures which got cancelled before reaching it.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::InstrumentFuture;
+    /// # use std::time::Duration;
+    /// # async fn foobar() {}
+    /// # async fn run() {
+    /// let res = InstrumentFuture::new(foobar())
+    ///     .threshold(Duration::from_millis(50))
+    ///     .await;
+    ///
+    /// if res.is_slow() {
+    ///     println!("foobar() is slow: {:?}", res.elapsed);
+    /// }
+    /// # }
+    /// ```
+    pub fn threshold(mut self, threshold: Duration) -> Self {
+        self.stats.threshold = Some(threshold);
+        self
+    }
+
+    /// Track how the wrapped future gets woken
+    ///
+    /// The waker passed to the wrapped future is wrapped in one which counts the wakes and notes when the
+    /// first wake since the previous poll happened. This shows how long the executor took to poll the future
+    /// after it was woken, which is where starved executors spend their time, as well as polls which happened
+    /// without the future being woken at all. The result is reported as [`InstrumentFutureResult::wakes`].
+    ///
+    /// Wakes are timed with the clock of the waking thread, so with [`MockClock`] the future should be woken
+    /// on the thread polling it.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::{InstrumentFuture, MockClock};
+    /// # use std::{cell::Cell, future::Future, pin::pin, task::{Context, Poll, Waker}, time::Duration};
+    /// let waker = Cell::new(None);
+    /// let mut polls = 0;
+    /// let fut = std::future::poll_fn(|cx| {
+    ///     polls += 1;
+    ///     waker.set(Some(cx.waker().clone()));
+    ///     if polls == 3 { Poll::Ready(()) } else { Poll::Pending }
+    /// });
+    ///
+    /// let mut fut = pin!(InstrumentFuture::<_, MockClock>::with_clock(fut).track_wakes());
+    /// let mut cx = Context::from_waker(Waker::noop());
+    ///
+    /// assert!(fut.as_mut().poll(&mut cx).is_pending());
+    ///
+    /// // woken, but only polled 5ms later
+    /// waker.take().unwrap().wake();
+    /// MockClock::advance(Duration::from_millis(5));
+    /// assert!(fut.as_mut().poll(&mut cx).is_pending());
+    ///
+    /// // polled without being woken
+    /// let Poll::Ready(res) = fut.as_mut().poll(&mut cx) else {
+    ///     unreachable!()
+    /// };
+    ///
+    /// let wakes = res.wakes.unwrap();
+    /// assert_eq!(wakes.wakes, 1);
+    /// assert_eq!(wakes.spurious_polls, 1);
+    /// assert_eq!(wakes.max_wake_latency, Duration::from_millis(5));
+    /// ```
+    pub fn track_wakes(mut self) -> Self
+    where
+        C: Send + Sync + 'static,
+    {
+        self.stats.wakes = Some(wake::WakeTracker::new::<C>());
+        self
+    }
+
+    /// Warn right away whenever a single poll of the wrapped future takes longer than `budget`
+    ///
+    /// A future which blocks inside `poll` stalls every other task on the same executor thread, which doesn't
+    /// show in its `elapsed` time. Each poll over the budget is logged as a warning with the site's name, file and line,
+    /// labels and the duration of the poll as soon as the poll returns, without waiting for the future to complete.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::InstrumentFuture;
+    /// # use std::time::Duration;
+    /// # async fn foobar() {}
+    /// # async fn run() {
+    /// InstrumentFuture::new(foobar())
+    ///     .name("foobar")
+    ///     .poll_budget(Duration::from_millis(10))
+    ///     .await;
+    /// # }
+    /// ```
+    pub fn poll_budget(mut self, budget: Duration) -> Self {
+        self.stats.poll_budget = Some(PollBudget {
+            budget,
+            backtrace: false,
+        });
+        self
+    }
+
+    /// Like [`poll_budget`](InstrumentFuture::poll_budget), but also log a backtrace with every warning
+    ///
+    /// The backtrace is captured once the poll returned, so it shows where the future was polled from
+    /// (e.g. which task and executor) rather than the blocking code itself. Capturing it is expensive, but
+    /// only happens for polls over the budget.
+    pub fn poll_budget_with_backtrace(mut self, budget: Duration) -> Self {
+        self.stats.poll_budget = Some(PollBudget {
+            budget,
+            backtrace: true,
+        });
+        self
+    }
+
+    /// Catch panics raised while polling the wrapped future and call `callback` before resuming them
+    ///
+    /// The callback receives the timing collected up until the panic along with where it was raised,
+    /// afterwards the panic continues unwinding as if it was never caught.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::InstrumentFuture;
+    /// # use std::{future::Future, panic, pin::pin, sync::mpsc, task::{Context, Waker}};
+    /// let (tx, rx) = mpsc::channel();
+    ///
+    /// let fut = InstrumentFuture::new(async { panic!("boom") }).on_panic(move |panicked| {
+    ///     tx.send(panicked).unwrap();
+    /// });
+    ///
+    /// let mut fut = pin!(fut);
+    /// let res = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+    ///     fut.as_mut().poll(&mut Context::from_waker(Waker::noop()))
+    /// }));
+    /// assert!(res.is_err());
+    ///
+    /// let panicked = rx.recv().unwrap();
+    /// assert_eq!(panicked.polls, 1);
+    /// assert_eq!(panicked.message.as_deref(), Some("boom"));
+    /// assert_eq!(panicked.location.file(), file!());
+    /// assert_eq!(panicked.panic_location.unwrap().file, file!());
+    /// ```
+    pub fn on_panic(
+        mut self,
+        callback: impl FnOnce(InstrumentFuturePanicked) + Send + Sync + 'static,
+    ) -> Self {
+        panic::install_hook();
+
+        self.on_panic = Some(Box::new(callback));
+        self
+    }
+
+    /// Make the future's span a child of `parent` instead of the span current when the future was created
+    ///
+    /// The span starts on the first poll and ends on completion, cancellation or panic. It's named
+    /// after the future, or its file and line, and carries the `polls`, `busy_ns` and `idle_ns`
+    /// of the future along with its `outcome`. While the future is polled its span is the current
+    /// span, so spans started inside of it become its children.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::InstrumentFuture;
+    /// # use opentelemetry::{global, trace::{TraceContextExt, Tracer}, Context};
+    /// # use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    /// # use std::{future::Future, pin::pin, task::{Context as TaskContext, Waker}};
+    /// let exporter = InMemorySpanExporter::default();
+    /// let provider = SdkTracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+    /// global::set_tracer_provider(provider);
+    ///
+    /// let parent = global::tracer("app").start("handle_request");
+    /// let parent = Context::current_with_span(parent);
+    ///
+    /// let fut = InstrumentFuture::new(async {}).name("fetch_user").otel_parent(parent.clone());
+    /// let _ = pin!(fut).poll(&mut TaskContext::from_waker(Waker::noop()));
+    ///
+    /// let spans = exporter.get_finished_spans().unwrap();
+    /// assert_eq!(spans[0].name, "fetch_user");
+    /// assert_eq!(spans[0].parent_span_id, parent.span().span_context().span_id());
+    /// ```
+    ///
+    /// Only available with the `opentelemetry` feature.
+    #[cfg(feature = "opentelemetry")]
+    pub fn otel_parent(mut self, parent: opentelemetry::Context) -> Self {
+        self.span = otel::OtelSpan::with_parent(parent);
+        self
+    }
 }
 
-impl<F: Future> Future for InstrumentFuture<F> {
+impl<F: Future + Debug, C: Clock + Debug> Debug for InstrumentFuture<F, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstrumentFuture")
+            .field("future", &self.future)
+            .field("stats", &self.stats)
+            .field("on_cancel", &self.on_cancel.as_ref().map(|_| ".."))
+            .field("on_panic", &self.on_panic.as_ref().map(|_| ".."))
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl<F: Future, C: Clock> Future for InstrumentFuture<F, C> {
     type Output = InstrumentFutureResult<<F as Future>::Output>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
 
-        if this.timer.is_none() {
-            *this.timer = Some(Instant::now());
-        }
+        let start = this.stats.start_poll();
+
+        let waker = this.stats.waker(cx.waker());
+        let cx = &mut Context::from_waker(waker.as_ref().unwrap_or(cx.waker()));
+
+        #[cfg(feature = "opentelemetry")]
+        let _span = this
+            .span
+            .enter(this.stats.name, this.stats.location, &this.stats.labels);
+
+        let poll = if this.on_panic.is_some() {
+            panic::clear_location();
+
+            let mut future = this.future;
+            match catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+                Ok(poll) => poll,
+                Err(payload) => {
+                    let end = this.stats.end_poll(start);
+                    let snapshot = this.stats.snapshot(end);
+                    *this.done = true;
 
-        this.future.poll(cx).map(|r| InstrumentFutureResult {
-            result: r,
-            // SAFETY: `timer` is always `Some(T)` since we ensure it's always set to Some above
-            elapsed: unsafe { this.timer.unwrap_unchecked() }.elapsed(),
+                    #[cfg(feature = "opentelemetry")]
+                    this.span.end(&snapshot, "panicked");
+
+                    if let Some(callback) = this.on_panic.take() {
+                        callback(InstrumentFuturePanicked {
+                            name: snapshot.name,
+                            labels: snapshot.labels,
+                            location: snapshot.location,
+                            elapsed: snapshot.elapsed,
+                            busy: snapshot.busy,
+                            idle: snapshot.idle,
+                            polls: snapshot.polls,
+                            panic_location: panic::last_location(),
+                            message: panic::message(&*payload),
+                        });
+                    }
+
+                    resume_unwind(payload);
+                }
+            }
+        } else {
+            this.future.poll(cx)
+        };
+
+        let end = this.stats.end_poll(start);
+
+        poll.map(|r| {
+            *this.done = true;
+            this.stats.in_flight = None;
+
+            #[cfg(feature = "opentelemetry")]
+            this.span.end(&this.stats.snapshot(end), "completed");
+
+            this.stats.result(r, end)
         })
     }
 }
 
+#[pinned_drop]
+impl<F: Future, C: Clock> PinnedDrop for InstrumentFuture<F, C> {
+    fn drop(self: Pin<&mut Self>) {
+        let this = self.project();
+
+        // a panic unwinding through the future isn't a cancellation
+        if *this.done || std::thread::panicking() {
+            return;
+        }
+
+        #[cfg(feature = "opentelemetry")]
+        this.span.end(&this.stats.snapshot(C::now()), "cancelled");
+
+        if let Some(callback) = this.on_cancel.take() {
+            let cancelled = this.stats.snapshot(C::now());
+
+            if this
+                .stats
+                .threshold
+                .is_none_or(|threshold| cancelled.elapsed > threshold)
+            {
+                callback(cancelled);
+            }
+        }
+    }
+}
+
+/// See [`InstrumentFuture::poll_budget`]
+#[derive(Debug, Clone, Copy)]
+struct PollBudget {
+    budget: Duration,
+    backtrace: bool,
+}
+
+/// Bookkeeping of the individual polls of an [`InstrumentFuture`]
+#[derive(Debug)]
+struct PollStats<C> {
+    name: Option<&'static str>,
+    labels: Vec<(&'static str, String)>,
+    location: &'static Location<'static>,
+    created: Option<C>,
+    first_poll: Option<C>,
+    busy: Duration,
+    polls: u64,
+    min_poll: Duration,
+    max_poll: Duration,
+    cpu_time: Option<Duration>,
+    cpu_start: Option<Duration>,
+    wakes: Option<wake::WakeTracker>,
+    poll_budget: Option<PollBudget>,
+    in_flight: Option<in_flight::Tracked>,
+    threshold: Option<Duration>,
+}
+
+impl<C: Clock> PollStats<C> {
+    fn new(location: &'static Location<'static>) -> Self {
+        Self {
+            name: None,
+            labels: Vec::new(),
+            location,
+            created: None,
+            first_poll: None,
+            busy: Duration::ZERO,
+            polls: 0,
+            min_poll: Duration::MAX,
+            max_poll: Duration::ZERO,
+            cpu_time: None,
+            cpu_start: None,
+            wakes: None,
+            poll_budget: None,
+            in_flight: None,
+            threshold: None,
+        }
+    }
+
+    fn start_poll(&mut self) -> C {
+        if self.polls == 0 {
+            self.in_flight = InFlight::global().track(self.name, self.location, &self.labels);
+        }
+
+        self.cpu_start = self.cpu_time.and_then(|_| clock::thread_cpu_time());
+
+        let start = C::now();
+        self.first_poll.get_or_insert(start);
+
+        start
+    }
+
+    /// The waker to poll the wrapped future with instead of `inner`, if wakes are tracked
+    fn waker(&mut self, inner: &Waker) -> Option<Waker> {
+        let first = self.polls == 0;
+        self.wakes.as_mut().map(|wakes| wakes.poll(inner, first))
+    }
+
+    fn end_poll(&mut self, start: C) -> C {
+        let end = C::now();
+
+        if let (Some(cpu_time), Some(cpu_start)) = (self.cpu_time.as_mut(), self.cpu_start) {
+            let cpu_end = clock::thread_cpu_time().unwrap_or(cpu_start);
+            *cpu_time += cpu_end.saturating_sub(cpu_start);
+        }
+
+        if let Some(in_flight) = &self.in_flight {
+            in_flight.polled();
+        }
+
+        let poll_time = end.duration_since(start);
+
+        if let Some(PollBudget { budget, backtrace }) = self.poll_budget {
+            if poll_time > budget {
+                let backtrace = backtrace.then(Backtrace::force_capture);
+                let site = Site::new(self.name, self.location.file(), self.location.line())
+                    .labels(&self.labels);
+                emit::blocking_poll(site, poll_time, budget, backtrace.as_ref());
+            }
+        }
+
+        self.busy += poll_time;
+        self.polls += 1;
+        self.min_poll = self.min_poll.min(poll_time);
+        self.max_poll = self.max_poll.max(poll_time);
+
+        end
+    }
+
+    fn result<R>(&self, result: R, end: C) -> InstrumentFutureResult<R> {
+        let InstrumentFutureCancelled {
+            name,
+            labels,
+            location,
+            elapsed,
+            time_to_first_poll,
+            busy,
+            idle,
+            polls,
+            min_poll,
+            max_poll,
+            mean_poll,
+            cpu_time,
+            wakes,
+        } = self.snapshot(end);
+
+        InstrumentFutureResult {
+            result,
+            name,
+            labels,
+            location,
+            elapsed,
+            time_to_first_poll,
+            busy,
+            idle,
+            polls,
+            min_poll,
+            max_poll,
+            mean_poll,
+            cpu_time,
+            wakes,
+            threshold: self.threshold,
+        }
+    }
+
+    fn snapshot(&self, end: C) -> InstrumentFutureCancelled {
+        let elapsed = self
+            .first_poll
+            .map_or(Duration::ZERO, |first_poll| end.duration_since(first_poll));
+
+        InstrumentFutureCancelled {
+            name: self.name,
+            labels: self.labels.clone(),
+            location: self.location,
+            elapsed,
+            time_to_first_poll: self
+                .created
+                .zip(self.first_poll)
+                .map(|(created, first_poll)| first_poll.duration_since(created)),
+            busy: self.busy,
+            idle: elapsed.saturating_sub(self.busy),
+            polls: self.polls,
+            min_poll: if self.polls == 0 {
+                Duration::ZERO
+            } else {
+                self.min_poll
+            },
+            max_poll: self.max_poll,
+            mean_poll: if self.polls == 0 {
+                Duration::ZERO
+            } else {
+                self.busy.div_f64(self.polls as f64)
+            },
+            cpu_time: self.cpu_time,
+            wakes: self.wakes.as_ref().map(wake::WakeTracker::stats),
+        }
+    }
+}
+
 /// Debug log how long a future took to execute
 ///
 /// When `debug_assertions` are enabled, does a log::debug!() with the file!(), line!(), and elapsed time.
@@ -72,40 +884,63 @@ impl<F: Future> Future for InstrumentFuture<F> {
 /// This allows you to use the created instrumenting future later if desired since it doesn't `await` immediately.
 ///
 /// There is also an optional one with a custom log message. `elapsed` is provided as a keyword arg to the literal,
-/// so you must use it somewhere in there.
+/// so you must use it somewhere in there. The labels, if any, are appended to it.
+///
+/// Like `log`'s own macros, a custom `target:` and level can be passed before the future (and log message).
+/// The level is a [`log::Level`] and can be picked at runtime. With the `tracing` feature enabled, the target
+/// has to be a constant, since `tracing` callsites are statics.
+///
+/// A `name = <&'static str>` identifies the future in the log message instead of its file and line, see
+/// [`InstrumentFuture::name`]. `labels = [<key> = <value>, ..]` attaches labels shown and exported along with
+/// it, see [`InstrumentFuture::label`]. Their values are evaluated before the future, and their keys are
+/// checked at compile time.
+///
+/// Passing `threshold = <Duration>` keeps the macro silent unless the future took longer than that, see
+/// [`InstrumentFuture::threshold`]. It only applies to the log message, the future is still recorded below.
+/// Together with `escalate = (<Level>, <Duration>)` the message is logged at the escalated level instead once
+/// the future took longer than the second duration.
+///
+/// If the returned future is dropped before it completed, a "cancelled after X (N polls)" message is logged instead.
+///
+/// Every completion is also recorded into the [global registry](Registry::global), regardless of the threshold.
+///
+/// With the `metrics` feature enabled, every completion and cancellation is also recorded into the installed
+/// [`metrics`](https://docs.rs/metrics) recorder as an `async_instrumenter_duration_seconds` histogram, labeled with
+/// `name`, `outcome` (`completed` or `cancelled`), `file` and `line`.
+///
+/// With the `tracing` feature enabled, a `tracing` event carrying `file`, `line`, `elapsed`, `busy`, `idle` and `polls`
+/// as structured fields is emitted instead of the `log` message.
 ///
 /// If you need custom behavior, you can make a custom instrumenting future using [`InstrumentFuture`]
 ///
 /// Examples:
 ///
 /// ```rust
-/// let my_fut: impl Future<Output = ()> = foobar();
+/// # use async_instrumenter::dbg_instrument;
+/// # use log::Level;
+/// # use std::time::Duration;
+/// # async fn foobar() {}
+/// # async fn run() {
+/// let my_fut = foobar();
 /// dbg_instrument!(my_fut).await;
 ///
 /// let f = 0;
-/// dbg_instrument!("custom_log_message {f}: {elapsed:?}", my_fut).await;
+/// dbg_instrument!("custom_log_message {f}: {elapsed:?}", foobar()).await;
+///
+/// dbg_instrument!(target: "slow_path", Level::Trace, foobar()).await;
+///
+/// dbg_instrument!(threshold = Duration::from_millis(50), foobar()).await;
+/// # }
 /// ```
 #[macro_export]
 macro_rules! dbg_instrument {
-    ($fut:expr) => {
+    ($($args:tt)+) => {
         async {
-            if cfg!(debug_assertions) {
-                $crate::instrument!($fut).await
-            } else {
-                $fut.await
-            }
+            $crate::_instrument!(
+                @parse [cfg!(debug_assertions)] [module_path!()] [$crate::log::Level::Debug] [] [] [] [] $($args)+
+            )
         }
     };
-
-    ($log:literal, $fut:expr) => {{
-        async {
-            if cfg!(debug_assertions) {
-                $crate::instrument!($log, $fut).await
-            } else {
-                $fut.await
-            }
-        }
-    }};
 }
 
 /// Debug log how long a future took to execute
@@ -116,50 +951,505 @@ macro_rules! dbg_instrument {
 /// This allows you to use the created instrumenting future later if desired since it doesn't `await` immediately.
 ///
 /// There is also an optional one with a custom log message. `elapsed` is provided as a keyword arg to the literal,
-/// so you must use it somewhere in there.
+/// so you must use it somewhere in there. The labels, if any, are appended to it.
+///
+/// Like `log`'s own macros, a custom `target:` and level can be passed before the future (and log message).
+/// The level is a [`log::Level`] and can be picked at runtime. With the `tracing` feature enabled, the target
+/// has to be a constant, since `tracing` callsites are statics.
+///
+/// A `name = <&'static str>` identifies the future in the log message instead of its file and line, see
+/// [`InstrumentFuture::name`]. `labels = [<key> = <value>, ..]` attaches labels shown and exported along with
+/// it, see [`InstrumentFuture::label`]. Their values are evaluated before the future, and their keys are
+/// checked at compile time.
+///
+/// Passing `threshold = <Duration>` keeps the macro silent unless the future took longer than that, see
+/// [`InstrumentFuture::threshold`]. It only applies to the log message, the future is still recorded below.
+/// Together with `escalate = (<Level>, <Duration>)` the message is logged at the escalated level instead once
+/// the future took longer than the second duration.
+///
+/// If the returned future is dropped before it completed, a "cancelled after X (N polls)" message is logged instead.
+///
+/// Every completion is also recorded into the [global registry](Registry::global), regardless of the threshold.
+///
+/// With the `metrics` feature enabled, every completion and cancellation is also recorded into the installed
+/// [`metrics`](https://docs.rs/metrics) recorder as an `async_instrumenter_duration_seconds` histogram, labeled with
+/// `name`, `outcome` (`completed` or `cancelled`), `file` and `line`.
+///
+/// With the `tracing` feature enabled, a `tracing` event carrying `file`, `line`, `elapsed`, `busy`, `idle` and `polls`
+/// as structured fields is emitted instead of the `log` message.
 ///
 /// If you need custom behavior, you can make a custom instrumenting future using [`InstrumentFuture`]
 ///
 /// Examples:
 ///
 /// ```rust
-/// let my_fut: impl Future<Output = ()> = foobar();
+/// # use async_instrumenter::instrument;
+/// # use log::Level;
+/// # use std::time::Duration;
+/// # async fn foobar() {}
+/// # async fn run() {
+/// let my_fut = foobar();
 /// instrument!(my_fut).await;
 ///
 /// let f = 0;
-/// instrument!("custom_log_message {f}: {elapsed:?}", my_fut).await;
+/// instrument!("custom_log_message {f}: {elapsed:?}", foobar()).await;
+///
+/// instrument!(Level::Info, foobar()).await;
+/// instrument!(target: "slow_path", Level::Warn, "took {elapsed:?}", foobar()).await;
+///
+/// let tenant = 42;
+/// instrument!(name = "foobar", labels = [endpoint = "/users", tenant = tenant], foobar()).await;
+///
+/// // only log if slower than 50ms, and warn if slower than 500ms
+/// instrument!(
+///     threshold = Duration::from_millis(50),
+///     escalate = (Level::Warn, Duration::from_millis(500)),
+///     foobar()
+/// )
+/// .await;
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// # use async_instrumenter::instrument;
+/// # async fn run() {
+/// // `le` is added by the Prometheus export
+/// instrument!(labels = [le = 1], async {}).await;
+/// # }
 /// ```
 #[macro_export]
 macro_rules! instrument {
-    ($fut:expr) => {
-        async { $crate::_instrument!($fut) }
+    ($($args:tt)+) => {
+        async {
+            $crate::_instrument!(@parse [] [module_path!()] [$crate::log::Level::Debug] [] [] [] [] $($args)+)
+        }
     };
+}
 
-    ($log:literal, $fut:expr) => {{
-        async { $crate::_instrument!($log, $fut) }
-    }};
+/// Log how long a stream took to yield its items
+///
+/// Works like [`instrument!`], taking the same options apart from `labels`, except it wraps a
+/// [`Stream`](futures_core::Stream) in an [`InstrumentStream`] and returns it. Once the stream ended, the total
+/// time, the number of items, the time until the first item and the longest gap between items are logged. A
+/// custom log message is only given `elapsed`, and since it's logged from a callback, any other variables it
+/// uses must be owned.
+///
+/// If the stream is dropped before it ended, a "stream cancelled after X (N items)" message is logged instead.
+///
+/// Only available with the `stream` feature.
+///
+/// ```rust
+/// # use async_instrumenter::instrument_stream;
+/// # use log::Level;
+/// # use std::{pin::Pin, task::{Context, Poll}};
+/// # struct Pages;
+/// # impl futures_core::Stream for Pages {
+/// #     type Item = u32;
+/// #     fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<u32>> { Poll::Ready(None) }
+/// # }
+/// # fn pages() -> Pages { Pages }
+/// let stream = instrument_stream!(pages());
+///
+/// let stream = instrument_stream!(Level::Info, name = "pages", "paginating took {elapsed:?}", pages());
+/// ```
+///
+/// ```compile_fail
+/// # use async_instrumenter::instrument_stream;
+/// # fn pages() -> futures_core::stream::Empty<u32> { unimplemented!() }
+/// // streams can't be labeled
+/// let stream = instrument_stream!(labels = [tenant = 42], pages());
+/// ```
+#[cfg(feature = "stream")]
+#[macro_export]
+macro_rules! instrument_stream {
+    ($($args:tt)+) => {
+        $crate::_instrument!(@parse [stream] [module_path!()] [$crate::log::Level::Debug] [] [] [] [] $($args)+)
+    };
 }
 
+// The arguments are parsed into bracketed slots, in order:
+// mode, target, level, threshold, escalate, name and labels
+//
+// The mode is `[]` to always instrument a future, `[<bool expr>]` to only instrument it if
+// the condition holds, or `[stream]` to instrument a stream instead of a future
 #[doc(hidden)]
 #[macro_export]
 macro_rules! _instrument {
-    ($fut:expr) => {{
-        let timed = $crate::InstrumentFuture::new($fut).await;
+    (@parse $enabled:tt $target:tt $level:tt $threshold:tt $escalate:tt $name:tt $labels:tt target: $t:expr, $($rest:tt)+) => {
+        $crate::_instrument!(@parse $enabled [$t] $level $threshold $escalate $name $labels $($rest)+)
+    };
 
-        let _file = file!();
-        let _line = line!();
-        let _elapsed = timed.elapsed;
+    (@parse $enabled:tt $target:tt $level:tt $threshold:tt $escalate:tt $name:tt $labels:tt threshold = $t:expr, $($rest:tt)+) => {
+        $crate::_instrument!(@parse $enabled $target $level [$t] $escalate $name $labels $($rest)+)
+    };
 
-        $crate::log::debug!("{_file}:{_line} completed in {_elapsed:?}");
+    (@parse $enabled:tt $target:tt $level:tt $threshold:tt $escalate:tt $name:tt $labels:tt escalate = ($l:expr, $t:expr), $($rest:tt)+) => {
+        $crate::_instrument!(@parse $enabled $target $level $threshold [($l, $t)] $name $labels $($rest)+)
+    };
+
+    (@parse $enabled:tt $target:tt $level:tt $threshold:tt $escalate:tt $name:tt $labels:tt name = $n:expr, $($rest:tt)+) => {
+        $crate::_instrument!(@parse $enabled $target $level $threshold $escalate [$n] $labels $($rest)+)
+    };
+
+    (@parse $enabled:tt $target:tt $level:tt $threshold:tt $escalate:tt $name:tt $labels:tt labels = [$($key:ident = $value:expr),* $(,)?], $($rest:tt)+) => {
+        $crate::_instrument!(@parse $enabled $target $level $threshold $escalate $name [$($key = $value),*] $($rest)+)
+    };
+
+    (@parse $enabled:tt $target:tt $level:tt $threshold:tt $escalate:tt $name:tt $labels:tt $log:literal, $fut:expr $(,)?) => {
+        $crate::_instrument!(@run $enabled $target $level $threshold $escalate $name $labels [$log] $fut)
+    };
+
+    (@parse $enabled:tt $target:tt $level:tt $threshold:tt $escalate:tt $name:tt $labels:tt $fut:expr $(,)?) => {
+        $crate::_instrument!(@run $enabled $target $level $threshold $escalate $name $labels [] $fut)
+    };
+
+    (@parse $enabled:tt $target:tt $level:tt $threshold:tt $escalate:tt $name:tt $labels:tt $l:expr, $($rest:tt)+) => {
+        $crate::_instrument!(@parse $enabled $target [$l] $threshold $escalate $name $labels $($rest)+)
+    };
+
+    (@run [stream] [$target:expr] [$level:expr] [$($threshold:expr)?] $escalate:tt [$($name:expr)?] [] $log:tt $stream:expr) => {
+        $crate::InstrumentStream::new($stream)
+            $(.name($name))?
+            $(.threshold($threshold))?
+            .on_complete(move |timed| {
+                $crate::_instrument!(@log stream_completed timed [[$target] $target] [$level] $escalate $log);
+            })
+            .on_cancel(move |cancelled| {
+                $crate::_log!(stream_cancelled cancelled, [$target] $target, $level);
+            })
+    };
+
+    (@run [stream] $target:tt $level:tt $threshold:tt $escalate:tt $name:tt [$($labels:tt)+] $log:tt $stream:expr) => {
+        compile_error!("`instrument_stream!` doesn't take labels")
+    };
+
+    (@run [$enabled:expr] $target:tt $level:tt $threshold:tt $escalate:tt $name:tt $labels:tt $log:tt $fut:expr) => {
+        if $enabled {
+            $crate::_instrument!(@run [] $target $level $threshold $escalate $name $labels $log $fut)
+        } else {
+            $fut.await
+        }
+    };
+
+    (@run [] [$target:expr] [$level:expr] [$($threshold:expr)?] $escalate:tt [$($name:expr)?] [$($key:ident = $value:expr),*] $log:tt $fut:expr) => {{
+        const _: () = {
+            $(
+                ::core::assert!(
+                    $crate::__private::is_valid_label_key(stringify!($key)),
+                    concat!("invalid label key `", stringify!($key), "`, it must not start with `__`")
+                );
+                ::core::assert!(
+                    !$crate::__private::is_reserved_label_key(stringify!($key)),
+                    concat!("label key `", stringify!($key), "` is reserved, as it's added on export")
+                );
+            )*
+        };
+
+        // evaluated ahead of the future, which might move the values used in them
+        let labels: ::std::vec::Vec<(&'static str, ::std::string::String)> =
+            ::std::vec![$((stringify!($key), ::std::string::ToString::to_string(&$value))),*];
+        // `tracing` takes the target tokens instead, since its callsites need a constant one
+        let _target: &str = $target;
+        let level: $crate::log::Level = $level;
+        // only applies to the log messages, every completion and cancellation is recorded
+        let threshold: ::core::option::Option<::core::time::Duration> =
+            ::core::option::Option::None $(.or(::core::option::Option::Some($threshold)))?;
+
+        // the callback can't borrow the target, so the cancellation is handed to a guard which logs it
+        // once the future got dropped, declared first to be dropped after it
+        let cancel = $crate::__private::CancelSlot::default();
+        let _guard = $crate::__private::OnDrop::new(|| {
+            if let Some(cancelled) = cancel.take() {
+                $crate::__private::record_cancelled(&cancelled);
+
+                if $crate::__private::exceeds(cancelled.elapsed, threshold) {
+                    $crate::_log!(cancelled cancelled, [$target] _target, level);
+                }
+            }
+        });
+
+        let timed = $crate::InstrumentFuture::new($fut)
+            $(.name($name))?
+            .labels(labels)
+            .on_cancel(cancel.sender())
+            .await;
+
+        $crate::__private::record_completed(&timed);
+
+        if $crate::__private::exceeds(timed.elapsed, threshold) {
+            $crate::_instrument!(@log completed timed [[$target] _target] [level] $escalate $log);
+        }
 
         timed.result
     }};
 
-    ($log:literal, $fut:expr) => {{
-        let timed = $crate::InstrumentFuture::new($fut).await;
+    (@log $kind:ident $timed:ident [$($target:tt)+] [$level:expr] [] $log:tt) => {
+        $crate::_log!($kind $timed, $($target)+, $level, $log);
+    };
 
-        $crate::log::debug!($log, elapsed = timed.elapsed);
+    (@log $kind:ident $timed:ident [$($target:tt)+] [$level:expr] [($escalated:expr, $above:expr)] $log:tt) => {
+        if $timed.elapsed > $above {
+            $crate::_log!($kind $timed, $($target)+, $escalated, $log);
+        } else {
+            $crate::_log!($kind $timed, $($target)+, $level, $log);
+        }
+    };
+}
 
-        timed.result
+#[doc(hidden)]
+#[cfg(not(feature = "tracing"))]
+#[macro_export]
+macro_rules! _log {
+    (completed $timed:ident, [$_target:expr] $target:expr, $level:expr, []) => {{
+        let _site = $crate::__private::Site::new($timed.name, file!(), line!()).labels(&$timed.labels);
+        let _elapsed = $timed.elapsed;
+        let _busy = $timed.busy;
+        let _idle = $timed.idle;
+        let _polls = $timed.polls;
+
+        $crate::log::log!(
+            target: $target,
+            $level,
+            "{_site} completed in {_elapsed:?} (busy {_busy:?}, idle {_idle:?}, {_polls} polls)"
+        );
     }};
+
+    (completed $timed:ident, [$_target:expr] $target:expr, $level:expr, [$log:literal]) => {
+        // labels follow the custom message, as they follow the site in the default one
+        if $timed.labels.is_empty() {
+            $crate::log::log!(target: $target, $level, $log, elapsed = $timed.elapsed);
+        } else {
+            $crate::log::log!(
+                target: $target,
+                $level,
+                "{} {}",
+                ::core::format_args!($log, elapsed = $timed.elapsed),
+                $crate::__private::Labels(&$timed.labels)
+            );
+        }
+    };
+
+    (cancelled $cancelled:ident, [$_target:expr] $target:expr, $level:expr) => {{
+        let _site = $crate::__private::Site::new($cancelled.name, file!(), line!()).labels(&$cancelled.labels);
+        let _elapsed = $cancelled.elapsed;
+        let _polls = $cancelled.polls;
+
+        $crate::log::log!(
+            target: $target,
+            $level,
+            "{_site} cancelled after {_elapsed:?} ({_polls} polls)"
+        );
+    }};
+
+    (stream_completed $timed:ident, [$_target:expr] $target:expr, $level:expr, []) => {{
+        let _site = $crate::__private::Site::new($timed.name, file!(), line!());
+        let _elapsed = $timed.elapsed;
+        let _items = $timed.items;
+        let _first = $timed.time_to_first_item.unwrap_or_default();
+        let _max_gap = $timed.max_gap;
+
+        $crate::log::log!(
+            target: $target,
+            $level,
+            "{_site} stream ended after {_elapsed:?} ({_items} items, first after {_first:?}, max gap {_max_gap:?})"
+        );
+    }};
+
+    (stream_completed $timed:ident, [$_target:expr] $target:expr, $level:expr, [$log:literal]) => {
+        $crate::log::log!(target: $target, $level, $log, elapsed = $timed.elapsed);
+    };
+
+    (stream_cancelled $cancelled:ident, [$_target:expr] $target:expr, $level:expr) => {{
+        let _site = $crate::__private::Site::new($cancelled.name, file!(), line!());
+        let _elapsed = $cancelled.elapsed;
+        let _items = $cancelled.items;
+
+        $crate::log::log!(
+            target: $target,
+            $level,
+            "{_site} stream cancelled after {_elapsed:?} ({_items} items)"
+        );
+    }};
+}
+
+/// `tracing` needs a constant level, so every level gets its own callsite and the level is matched at runtime
+#[doc(hidden)]
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! _event {
+    (target: $target:expr, $level:expr, $($args:tt)+) => {
+        match $level {
+            $crate::log::Level::Error => $crate::tracing::event!(target: $target, $crate::tracing::Level::ERROR, $($args)+),
+            $crate::log::Level::Warn => $crate::tracing::event!(target: $target, $crate::tracing::Level::WARN, $($args)+),
+            $crate::log::Level::Info => $crate::tracing::event!(target: $target, $crate::tracing::Level::INFO, $($args)+),
+            $crate::log::Level::Debug => $crate::tracing::event!(target: $target, $crate::tracing::Level::DEBUG, $($args)+),
+            $crate::log::Level::Trace => $crate::tracing::event!(target: $target, $crate::tracing::Level::TRACE, $($args)+),
+        }
+    };
+}
+
+#[doc(hidden)]
+#[cfg(feature = "tracing")]
+#[macro_export]
+macro_rules! _log {
+    (completed $timed:ident, [$target:expr] $_target:expr, $level:expr, []) => {
+        $crate::_event!(
+            target: $target,
+            $level,
+            name = $timed.name,
+            file = file!(),
+            line = line!(),
+            labels = %$crate::__private::Labels(&$timed.labels),
+            elapsed = ?$timed.elapsed,
+            busy = ?$timed.busy,
+            idle = ?$timed.idle,
+            polls = $timed.polls,
+            "completed"
+        );
+    };
+
+    (completed $timed:ident, [$target:expr] $_target:expr, $level:expr, [$log:literal]) => {
+        $crate::_event!(
+            target: $target,
+            $level,
+            name = $timed.name,
+            file = file!(),
+            line = line!(),
+            labels = %$crate::__private::Labels(&$timed.labels),
+            elapsed = ?$timed.elapsed,
+            busy = ?$timed.busy,
+            idle = ?$timed.idle,
+            polls = $timed.polls,
+            $log,
+            elapsed = $timed.elapsed
+        );
+    };
+
+    (cancelled $cancelled:ident, [$target:expr] $_target:expr, $level:expr) => {
+        $crate::_event!(
+            target: $target,
+            $level,
+            name = $cancelled.name,
+            file = file!(),
+            line = line!(),
+            labels = %$crate::__private::Labels(&$cancelled.labels),
+            elapsed = ?$cancelled.elapsed,
+            busy = ?$cancelled.busy,
+            idle = ?$cancelled.idle,
+            polls = $cancelled.polls,
+            "cancelled"
+        );
+    };
+
+    (stream_completed $timed:ident, [$target:expr] $_target:expr, $level:expr, []) => {
+        $crate::_event!(
+            target: $target,
+            $level,
+            name = $timed.name,
+            file = file!(),
+            line = line!(),
+            elapsed = ?$timed.elapsed,
+            time_to_first_item = ?$timed.time_to_first_item,
+            items = $timed.items,
+            min_gap = ?$timed.min_gap,
+            max_gap = ?$timed.max_gap,
+            mean_gap = ?$timed.mean_gap,
+            "stream ended"
+        );
+    };
+
+    (stream_completed $timed:ident, [$target:expr] $_target:expr, $level:expr, [$log:literal]) => {
+        $crate::_event!(
+            target: $target,
+            $level,
+            name = $timed.name,
+            file = file!(),
+            line = line!(),
+            elapsed = ?$timed.elapsed,
+            time_to_first_item = ?$timed.time_to_first_item,
+            items = $timed.items,
+            min_gap = ?$timed.min_gap,
+            max_gap = ?$timed.max_gap,
+            mean_gap = ?$timed.mean_gap,
+            $log,
+            elapsed = $timed.elapsed
+        );
+    };
+
+    (stream_cancelled $cancelled:ident, [$target:expr] $_target:expr, $level:expr) => {
+        $crate::_event!(
+            target: $target,
+            $level,
+            name = $cancelled.name,
+            file = file!(),
+            line = line!(),
+            elapsed = ?$cancelled.elapsed,
+            time_to_first_item = ?$cancelled.time_to_first_item,
+            items = $cancelled.items,
+            "stream cancelled"
+        );
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::emit::{is_reserved_label_key, is_valid_label_key, Labels, Site};
+
+    use std::{
+        sync::{Arc, Mutex, PoisonError},
+        time::Duration,
+    };
+
+    use crate::{InstrumentFutureCancelled, InstrumentFutureResult, Registry};
+
+    /// Record a completion of the macros wherever it's aggregated
+    ///
+    /// Features are checked here rather than in the macros, where they'd be the caller's features
+    pub fn record_completed<R>(res: &InstrumentFutureResult<R>) {
+        Registry::global().record_result(res);
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::completed(res);
+    }
+
+    /// Record a cancellation of the macros wherever it's aggregated
+    pub fn record_cancelled(_cancelled: &InstrumentFutureCancelled) {
+        #[cfg(feature = "metrics")]
+        crate::metrics::cancelled(_cancelled);
+    }
+
+    /// Whether `elapsed` is above the macros' `threshold`, if they were given one
+    pub fn exceeds(elapsed: Duration, threshold: Option<Duration>) -> bool {
+        threshold.is_none_or(|threshold| elapsed > threshold)
+    }
+
+    /// Where the `on_cancel` callback of the macros leaves the cancellation, to be logged by their guard
+    #[derive(Default)]
+    pub struct CancelSlot(Arc<Mutex<Option<InstrumentFutureCancelled>>>);
+
+    impl CancelSlot {
+        pub fn sender(&self) -> impl FnOnce(InstrumentFutureCancelled) + Send + Sync + 'static {
+            let slot = Arc::clone(&self.0);
+            move |cancelled| *slot.lock().unwrap_or_else(PoisonError::into_inner) = Some(cancelled)
+        }
+
+        pub fn take(&self) -> Option<InstrumentFutureCancelled> {
+            self.0.lock().unwrap_or_else(PoisonError::into_inner).take()
+        }
+    }
+
+    /// Run a closure once dropped
+    pub struct OnDrop<F: FnMut()>(F);
+
+    impl<F: FnMut()> OnDrop<F> {
+        pub fn new(f: F) -> Self {
+            Self(f)
+        }
+    }
+
+    impl<F: FnMut()> Drop for OnDrop<F> {
+        fn drop(&mut self) {
+            (self.0)()
+        }
+    }
 }
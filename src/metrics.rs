@@ -0,0 +1,53 @@
+use std::panic::Location;
+
+use ::metrics::Label;
+
+use crate::{InstrumentFutureCancelled, InstrumentFutureResult};
+
+const METRIC: &str = "async_instrumenter_duration_seconds";
+
+/// Record a completed future into the installed `metrics` recorder
+pub(crate) fn completed<R>(res: &InstrumentFutureResult<R>) {
+    record(
+        res.name,
+        res.location,
+        &res.labels,
+        "completed",
+        res.elapsed.as_secs_f64(),
+    );
+}
+
+/// Record a cancelled future into the installed `metrics` recorder
+pub(crate) fn cancelled(cancelled: &InstrumentFutureCancelled) {
+    record(
+        cancelled.name,
+        cancelled.location,
+        &cancelled.labels,
+        "cancelled",
+        cancelled.elapsed.as_secs_f64(),
+    );
+}
+
+fn record(
+    name: Option<&'static str>,
+    location: &'static Location<'static>,
+    labels: &[(&'static str, String)],
+    outcome: &'static str,
+    elapsed: f64,
+) {
+    let labels = [
+        Label::new("name", name.unwrap_or_default()),
+        Label::new("outcome", outcome),
+        Label::new("file", location.file()),
+        Label::new("line", location.line().to_string()),
+    ]
+    .into_iter()
+    .chain(
+        labels
+            .iter()
+            .map(|(key, value)| Label::new(*key, value.clone())),
+    )
+    .collect::<Vec<_>>();
+
+    ::metrics::histogram!(METRIC, labels).record(elapsed);
+}
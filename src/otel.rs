@@ -0,0 +1,96 @@
+use std::{panic::Location, time::SystemTime};
+
+use opentelemetry::{
+    global,
+    trace::{SpanKind, Status, TraceContextExt, Tracer},
+    Context, ContextGuard, KeyValue,
+};
+
+use crate::{emit::Site, InstrumentFutureCancelled};
+
+const TRACER: &str = "async-instrumenter";
+
+/// The OpenTelemetry span of an [`InstrumentFuture`](crate::InstrumentFuture)
+///
+/// The span is only started on the first poll, so it covers the execution time like `elapsed` does,
+/// and so the name given after creating the future is used for it.
+#[derive(Debug)]
+pub(crate) struct OtelSpan {
+    parent: Context,
+    cx: Option<Context>,
+}
+
+impl OtelSpan {
+    /// A span which will be a child of the current span
+    pub(crate) fn new() -> Self {
+        Self::with_parent(Context::current())
+    }
+
+    pub(crate) fn with_parent(parent: Context) -> Self {
+        Self { parent, cx: None }
+    }
+
+    /// Start the span if this is the first poll, and make it the current span until the guard is dropped
+    pub(crate) fn enter(
+        &mut self,
+        name: Option<&'static str>,
+        location: &'static Location<'static>,
+        labels: &[(&'static str, String)],
+    ) -> ContextGuard {
+        let parent = &self.parent;
+
+        let cx = self.cx.get_or_insert_with(|| {
+            let tracer = global::tracer(TRACER);
+
+            let span = tracer
+                .span_builder(Site::new(name, location.file(), location.line()).to_string())
+                .with_kind(SpanKind::Internal)
+                .with_start_time(SystemTime::now())
+                .with_attributes(
+                    [
+                        KeyValue::new("code.filepath", location.file()),
+                        KeyValue::new("code.lineno", i64::from(location.line())),
+                    ]
+                    .into_iter()
+                    .chain(
+                        labels
+                            .iter()
+                            .map(|(key, value)| KeyValue::new(*key, value.clone())),
+                    ),
+                )
+                .start_with_context(&tracer, parent);
+
+            parent.with_span(span)
+        });
+
+        cx.clone().attach()
+    }
+
+    /// End the span, if it was started, with the timings of the future
+    pub(crate) fn end(&mut self, timing: &InstrumentFutureCancelled, outcome: &'static str) {
+        let Some(cx) = self.cx.take() else {
+            return;
+        };
+
+        let span = cx.span();
+
+        span.set_attributes([
+            KeyValue::new("outcome", outcome),
+            KeyValue::new("polls", i64::try_from(timing.polls).unwrap_or(i64::MAX)),
+            KeyValue::new(
+                "busy_ns",
+                i64::try_from(timing.busy.as_nanos()).unwrap_or(i64::MAX),
+            ),
+            KeyValue::new(
+                "idle_ns",
+                i64::try_from(timing.idle.as_nanos()).unwrap_or(i64::MAX),
+            ),
+        ]);
+
+        if outcome == "panicked" {
+            span.set_status(Status::error("the future panicked"));
+        }
+
+        span.end_with_timestamp(SystemTime::now());
+    }
+}
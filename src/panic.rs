@@ -0,0 +1,73 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    fmt::{self, Display},
+    panic,
+    sync::Once,
+};
+
+/// Where in the source code a panic was raised
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PanicLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Display for PanicLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+thread_local! {
+    static LAST_LOCATION: RefCell<Option<PanicLocation>> = const { RefCell::new(None) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Install a panic hook recording the location of every panic for [`last_location`]
+///
+/// The previously installed hook is still called afterwards. If the hook gets replaced later on,
+/// panic locations simply aren't available anymore.
+pub(crate) fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let prev = panic::take_hook();
+
+        panic::set_hook(Box::new(move |info| {
+            if let Some(location) = info.location() {
+                let location = PanicLocation {
+                    file: location.file().to_owned(),
+                    line: location.line(),
+                    column: location.column(),
+                };
+
+                // the thread local may already be gone if we panic during thread teardown
+                let _ = LAST_LOCATION.try_with(|last| *last.borrow_mut() = Some(location));
+            }
+
+            prev(info);
+        }));
+    });
+}
+
+/// Forget the location of the last panic on this thread, so an older one can't be mistaken for the next
+pub(crate) fn clear_location() {
+    LAST_LOCATION.with(|last| *last.borrow_mut() = None);
+}
+
+/// The location of the last panic on this thread recorded by the hook
+///
+/// The location is left in place, since the panic may be caught and resumed by several nested
+/// futures on its way up, and the hook doesn't run again for `resume_unwind`.
+pub(crate) fn last_location() -> Option<PanicLocation> {
+    LAST_LOCATION.with(|last| last.borrow().clone())
+}
+
+/// The message of a panic, if it was raised with one
+pub(crate) fn message(payload: &(dyn Any + Send)) -> Option<String> {
+    payload
+        .downcast_ref::<&str>()
+        .map(|msg| (*msg).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+}
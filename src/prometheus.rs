@@ -0,0 +1,216 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration,
+};
+
+use crate::Registry;
+
+const METRIC: &str = "async_instrumenter_duration_seconds";
+
+/// The `le` bounds of the exported histogram buckets, from 100µs to 10s
+const BUCKETS: [(Duration, &str); 16] = [
+    (Duration::from_micros(100), "0.0001"),
+    (Duration::from_micros(250), "0.00025"),
+    (Duration::from_micros(500), "0.0005"),
+    (Duration::from_millis(1), "0.001"),
+    (Duration::from_micros(2500), "0.0025"),
+    (Duration::from_millis(5), "0.005"),
+    (Duration::from_millis(10), "0.01"),
+    (Duration::from_millis(25), "0.025"),
+    (Duration::from_millis(50), "0.05"),
+    (Duration::from_millis(100), "0.1"),
+    (Duration::from_millis(250), "0.25"),
+    (Duration::from_millis(500), "0.5"),
+    (Duration::from_secs(1), "1.0"),
+    (Duration::from_millis(2500), "2.5"),
+    (Duration::from_secs(5), "5.0"),
+    (Duration::from_secs(10), "10.0"),
+];
+
+/// The exported counters of a single site, which a [`Registry::reset`] leaves alone
+///
+/// Unlike the registry's histograms these count into the exported buckets directly, so
+/// the counts are exact.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Cumulative {
+    /// How many elapsed times fell into each bucket, not including the ones below it
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: Duration,
+}
+
+impl Cumulative {
+    pub(crate) fn record(&mut self, elapsed: Duration) {
+        if let Some(i) = BUCKETS.iter().position(|(bound, _)| elapsed <= *bound) {
+            self.buckets[i] += 1;
+        }
+
+        self.count += 1;
+        self.sum += elapsed;
+    }
+
+    pub(crate) fn merge(&mut self, other: &Cumulative) {
+        for (count, other) in self.buckets.iter_mut().zip(other.buckets) {
+            *count += other;
+        }
+
+        self.count += other.count;
+        self.sum += other.sum;
+    }
+}
+
+impl Registry {
+    /// Render every site as an OpenMetrics histogram, labeled by its key
+    ///
+    /// The sites become the `site` label of a single `async_instrumenter_duration_seconds` histogram,
+    /// followed by their own labels, with buckets from 100µs to 10s, which Prometheus can scrape as is.
+    /// The counts are exact and keep counting up for the lifetime of the registry, even when it's
+    /// [`reset`](Registry::reset), e.g. by a [`Reporter`](crate::Reporter) on the same registry.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::Registry;
+    /// # use std::time::Duration;
+    /// let registry = Registry::new();
+    /// registry.record("fetch_user", Duration::from_millis(3));
+    ///
+    /// let text = registry.openmetrics();
+    /// assert!(text.contains(r#"async_instrumenter_duration_seconds_bucket{site="fetch_user",le="0.0025"} 0"#));
+    /// assert!(text.contains(r#"async_instrumenter_duration_seconds_bucket{site="fetch_user",le="0.005"} 1"#));
+    /// assert!(text.contains(r#"async_instrumenter_duration_seconds_count{site="fetch_user"} 1"#));
+    ///
+    /// registry.record_labeled("fetch_user", &[("tenant", "42")], Duration::from_millis(3));
+    /// let text = registry.openmetrics();
+    /// assert!(text.contains(r#"async_instrumenter_duration_seconds_count{site="fetch_user",tenant="42"} 1"#));
+    /// assert!(text.ends_with("# EOF\n"));
+    ///
+    /// // bounds are inclusive, and resets don't affect the counters
+    /// registry.record("fetch_user", Duration::from_millis(1));
+    /// registry.reset();
+    /// let text = registry.openmetrics();
+    /// assert!(text.contains(r#"async_instrumenter_duration_seconds_bucket{site="fetch_user",le="0.001"} 1"#));
+    /// assert!(text.contains(r#"async_instrumenter_duration_seconds_count{site="fetch_user"} 2"#));
+    /// ```
+    ///
+    /// Only available with the `prometheus` feature.
+    pub fn openmetrics(&self) -> String {
+        let mut text = String::new();
+
+        let _ = writeln!(text, "# TYPE {METRIC} histogram");
+        let _ = writeln!(text, "# UNIT {METRIC} seconds");
+        let _ = writeln!(
+            text,
+            "# HELP {METRIC} Elapsed time of instrumented futures."
+        );
+
+        for (key, labels, cumulative) in self.cumulative() {
+            write_site(&mut text, &key, &labels, &cumulative);
+        }
+
+        text.push_str("# EOF\n");
+        text
+    }
+
+    /// Serve [`openmetrics`](Registry::openmetrics) over HTTP on `addr` from a background thread
+    ///
+    /// Every request gets the current metrics, regardless of its path, and every connection is handled
+    /// on its own thread. This is meant for a local scrape endpoint and not for exposing to the outside.
+    /// The server runs for the rest of the process, and the address it's listening on is returned, e.g.
+    /// to find out the port picked when binding to port 0.
+    ///
+    /// ```rust
+    /// # use async_instrumenter::Registry;
+    /// # use std::{io::{Read, Write}, net::TcpStream, time::Duration};
+    /// static REGISTRY: Registry = Registry::new();
+    /// REGISTRY.record("fetch_user", Duration::from_millis(3));
+    ///
+    /// let addr = REGISTRY.serve_openmetrics("127.0.0.1:0").unwrap();
+    ///
+    /// let mut stream = TcpStream::connect(addr).unwrap();
+    /// stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    ///
+    /// let mut response = String::new();
+    /// stream.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 200 OK"));
+    /// assert!(response.contains(r#"async_instrumenter_duration_seconds_count{site="fetch_user"} 1"#));
+    /// ```
+    ///
+    /// Only available with the `prometheus` feature.
+    pub fn serve_openmetrics(&'static self, addr: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+
+        thread::Builder::new()
+            .name("async-instrumenter-metrics".to_owned())
+            .spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    // a client going away mid-response isn't worth bringing the server down over,
+                    // and neither is running out of threads for one
+                    let _ = thread::Builder::new()
+                        .name("async-instrumenter-metrics-conn".to_owned())
+                        .spawn(move || self.respond(stream));
+                }
+            })?;
+
+        Ok(addr)
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+        // read until the end of the request head, the request itself doesn't matter
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") && request.len() < 8192 {
+            match stream.read(&mut buf)? {
+                0 => break,
+                n => request.extend_from_slice(&buf[..n]),
+            }
+        }
+
+        let body = self.openmetrics();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\
+             \r\n\
+             {body}",
+            body.len()
+        )?;
+
+        stream.flush()
+    }
+}
+
+fn write_site(text: &mut String, key: &str, site_labels: &[(String, String)], site: &Cumulative) {
+    let mut labels = format!("site=\"{}\"", escape(key));
+    for (key, value) in site_labels {
+        let _ = write!(labels, ",{key}=\"{}\"", escape(value));
+    }
+
+    let mut cumulative = 0;
+
+    for ((_, le), count) in BUCKETS.iter().zip(site.buckets) {
+        cumulative += count;
+        let _ = writeln!(text, "{METRIC}_bucket{{{labels},le=\"{le}\"}} {cumulative}");
+    }
+
+    let _ = writeln!(
+        text,
+        "{METRIC}_bucket{{{labels},le=\"+Inf\"}} {}",
+        site.count
+    );
+    let _ = writeln!(text, "{METRIC}_count{{{labels}}} {}", site.count);
+    let _ = writeln!(text, "{METRIC}_sum{{{labels}}} {}", site.sum.as_secs_f64());
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
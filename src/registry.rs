@@ -0,0 +1,325 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+#[cfg(feature = "prometheus")]
+use crate::prometheus::Cumulative;
+use crate::{emit, Bucket, Histogram, InstrumentFutureResult};
+
+static GLOBAL: Registry = Registry::new();
+
+/// Aggregated timing statistics of instrumented sites
+///
+/// Every completion of an [`instrument!`](crate::instrument) (and [`dbg_instrument!`](crate::dbg_instrument))
+/// is recorded into the [global](Registry::global) registry, keyed by the name given to it, or its file and
+/// line otherwise. Futures with [labels](crate::InstrumentFuture::label) are kept apart by their labels within
+/// the same key. Registries can also be created and fed by hand, e.g. from an [`InstrumentFutureResult`].
+///
+/// ```rust
+/// # use async_instrumenter::Registry;
+/// # use std::time::Duration;
+/// let registry = Registry::new();
+///
+/// registry.record("fetch_user", Duration::from_millis(5));
+/// registry.record("fetch_user", Duration::from_millis(15));
+///
+/// let snapshot = registry.snapshot();
+/// assert_eq!(snapshot.sites[0].key, "fetch_user");
+/// assert_eq!(snapshot.sites[0].count, 2);
+/// assert_eq!(snapshot.sites[0].mean, Duration::from_millis(10));
+///
+/// let p50 = registry.histogram("fetch_user").unwrap().percentile(50.0);
+/// assert!(p50.abs_diff(Duration::from_millis(5)) < Duration::from_micros(200));
+///
+/// registry.record_labeled("fetch_user", &[("tenant", "42")], Duration::from_millis(20));
+/// assert_eq!(registry.snapshot().sites[1].labels, [("tenant".to_owned(), "42".to_owned())]);
+///
+/// // start a new reporting interval
+/// registry.reset();
+/// assert!(registry.snapshot().sites.is_empty());
+/// ```
+///
+/// ```rust
+/// # use async_instrumenter::{InstrumentFuture, Registry};
+/// # async fn fetch_user() {}
+/// # async fn run() {
+/// static REGISTRY: Registry = Registry::new();
+///
+/// let res = InstrumentFuture::new(fetch_user()).name("fetch_user").await;
+/// REGISTRY.record_result(&res);
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Registry {
+    sites: Mutex<BTreeMap<String, BTreeMap<Labels, Site>>>,
+}
+
+type Labels = Vec<(String, String)>;
+
+/// Everything recorded for a single site with the same labels
+#[derive(Debug, Clone, Default)]
+struct Site {
+    /// The elapsed times since the last reset
+    window: Histogram,
+    /// The exported counters, which have to keep counting up across resets
+    #[cfg(feature = "prometheus")]
+    cumulative: Cumulative,
+}
+
+impl Site {
+    fn record(&mut self, elapsed: Duration) {
+        self.window.record(elapsed);
+
+        #[cfg(feature = "prometheus")]
+        self.cumulative.record(elapsed);
+    }
+
+    fn merge(&mut self, other: &Site) {
+        self.window.merge(&other.window);
+
+        #[cfg(feature = "prometheus")]
+        self.cumulative.merge(&other.cumulative);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.window.count() == 0
+    }
+}
+
+impl Registry {
+    pub const fn new() -> Self {
+        Self {
+            sites: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// The registry the macros record into
+    pub fn global() -> &'static Registry {
+        &GLOBAL
+    }
+
+    /// Record a single elapsed time for the site `key`
+    pub fn record(&self, key: &str, elapsed: Duration) {
+        self.record_labeled::<&str, &str>(key, &[], elapsed);
+    }
+
+    /// Record a single elapsed time for the site `key` with `labels`
+    ///
+    /// Panics if a label key is invalid or reserved, see [`InstrumentFuture::label`](crate::InstrumentFuture::label).
+    #[track_caller]
+    pub fn record_labeled<K: AsRef<str>, V: AsRef<str>>(
+        &self,
+        key: &str,
+        labels: &[(K, V)],
+        elapsed: Duration,
+    ) {
+        let labels = labels
+            .iter()
+            .map(|(key, value)| {
+                emit::check_label_key(key.as_ref());
+                (key.as_ref().to_owned(), value.as_ref().to_owned())
+            })
+            .collect::<Labels>();
+
+        let mut sites = self.sites.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let site = match sites.get_mut(key) {
+            Some(site) => site,
+            None => sites.entry(key.to_owned()).or_default(),
+        };
+
+        site.entry(labels).or_default().record(elapsed);
+    }
+
+    /// Record the elapsed time of a finished [`InstrumentFuture`](crate::InstrumentFuture)
+    ///
+    /// The site is keyed by the future's name, or by the file and line it was created at otherwise. Panics like
+    /// [`Registry::record_labeled`] if one of its labels has an invalid or reserved key.
+    #[track_caller]
+    pub fn record_result<R>(&self, res: &InstrumentFutureResult<R>) {
+        match res.name {
+            Some(name) => self.record_labeled(name, &res.labels, res.elapsed),
+            None => {
+                let key = format!("{}:{}", res.location.file(), res.location.line());
+                self.record_labeled(&key, &res.labels, res.elapsed);
+            }
+        }
+    }
+
+    /// The histogram of elapsed times recorded for the site `key` without labels
+    pub fn histogram(&self, key: &str) -> Option<Histogram> {
+        self.histogram_labeled::<&str, &str>(key, &[])
+    }
+
+    /// The histogram of elapsed times recorded for the site `key` with `labels`
+    pub fn histogram_labeled<K: AsRef<str>, V: AsRef<str>>(
+        &self,
+        key: &str,
+        labels: &[(K, V)],
+    ) -> Option<Histogram> {
+        let sites = self.sites.lock().unwrap_or_else(PoisonError::into_inner);
+
+        sites.get(key)?.iter().find_map(|(site_labels, site)| {
+            let matches = site_labels.len() == labels.len()
+                && site_labels
+                    .iter()
+                    .zip(labels)
+                    .all(|((a, b), (c, d))| a == c.as_ref() && b == d.as_ref());
+
+            (matches && !site.is_empty()).then(|| site.window.clone())
+        })
+    }
+
+    /// Add everything recorded in `other` to this registry
+    pub fn merge(&self, other: &Registry) {
+        let other = other
+            .sites
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let mut sites = self.sites.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for (key, other) in other {
+            let site = sites.entry(key).or_default();
+
+            for (labels, other) in other {
+                site.entry(labels).or_default().merge(&other);
+            }
+        }
+    }
+
+    /// A summary of every site recorded so far, sorted by key and then labels
+    pub fn snapshot(&self) -> RegistrySnapshot {
+        let sites = self.sites.lock().unwrap_or_else(PoisonError::into_inner);
+
+        RegistrySnapshot {
+            sites: sites
+                .iter()
+                .flat_map(|(key, site)| {
+                    site.iter()
+                        .filter(|(_, site)| !site.is_empty())
+                        .map(|(labels, site)| {
+                            SiteSummary::new(key.clone(), labels.clone(), &site.window)
+                        })
+                })
+                .collect(),
+        }
+    }
+
+    /// Forget everything recorded so far, e.g. to start a new reporting interval
+    ///
+    /// The counters exported with the `prometheus` feature are kept, since they have to be monotonic.
+    pub fn reset(&self) {
+        let mut sites = self.sites.lock().unwrap_or_else(PoisonError::into_inner);
+
+        #[cfg(feature = "prometheus")]
+        for site in sites.values_mut().flat_map(BTreeMap::values_mut) {
+            site.window = Histogram::default();
+        }
+
+        #[cfg(not(feature = "prometheus"))]
+        sites.clear();
+    }
+
+    /// Take a [`snapshot`](Registry::snapshot) and [`reset`](Registry::reset) at once, so no
+    /// recording falls in between
+    pub fn snapshot_and_reset(&self) -> RegistrySnapshot {
+        let mut sites = self.sites.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let snapshot = RegistrySnapshot {
+            sites: sites
+                .iter_mut()
+                .flat_map(|(key, site)| {
+                    site.iter_mut()
+                        .filter(|(_, site)| !site.is_empty())
+                        .map(|(labels, site)| {
+                            let window = std::mem::take(&mut site.window);
+                            SiteSummary::new(key.clone(), labels.clone(), &window)
+                        })
+                })
+                .collect(),
+        };
+
+        #[cfg(not(feature = "prometheus"))]
+        sites.clear();
+
+        snapshot
+    }
+
+    /// The exported counters of every site recorded so far, sorted by key and then labels
+    #[cfg(feature = "prometheus")]
+    pub(crate) fn cumulative(&self) -> Vec<(String, Labels, Cumulative)> {
+        let sites = self.sites.lock().unwrap_or_else(PoisonError::into_inner);
+
+        sites
+            .iter()
+            .flat_map(|(key, site)| {
+                site.iter()
+                    .map(|(labels, site)| (key.clone(), labels.clone(), site.cumulative.clone()))
+            })
+            .collect()
+    }
+}
+
+/// A summary of all sites in a [`Registry`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct RegistrySnapshot {
+    pub sites: Vec<SiteSummary>,
+}
+
+/// A summary of the elapsed times recorded for a single site
+///
+/// The percentiles are taken from its [`Histogram`], so they have the same precision
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct SiteSummary {
+    /// The site's name, or its file and line
+    pub key: String,
+    /// The labels of the futures recorded for this site
+    pub labels: Vec<(String, String)>,
+    /// How many times the site completed
+    pub count: u64,
+    /// The total of all elapsed times
+    pub sum: Duration,
+    /// The shortest elapsed time
+    pub min: Duration,
+    /// The longest elapsed time
+    pub max: Duration,
+    /// The average elapsed time
+    pub mean: Duration,
+    /// The median elapsed time
+    pub p50: Duration,
+    /// The 90th percentile of the elapsed times
+    pub p90: Duration,
+    /// The 99th percentile of the elapsed times
+    pub p99: Duration,
+    /// The 99.9th percentile of the elapsed times
+    pub p999: Duration,
+    /// The non-empty buckets of the elapsed time histogram
+    pub histogram: Vec<Bucket>,
+}
+
+impl SiteSummary {
+    fn new(key: String, labels: Labels, histogram: &Histogram) -> Self {
+        Self {
+            key,
+            labels,
+            count: histogram.count(),
+            sum: histogram.sum(),
+            min: histogram.min(),
+            max: histogram.max(),
+            mean: histogram.mean(),
+            p50: histogram.percentile(50.0),
+            p90: histogram.percentile(90.0),
+            p99: histogram.percentile(99.0),
+            p999: histogram.percentile(99.9),
+            histogram: histogram.buckets().collect(),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    cmp::Reverse,
+    fmt::Write,
+    future::Future,
+    io,
+    time::{Duration, Instant},
+};
+
+use log::Level;
+
+use crate::{
+    background::{self, BackgroundHandle},
+    emit::{self, Labels},
+    Registry, SiteSummary,
+};
+
+/// Periodically logs a summary of the slowest and most frequent sites in a [`Registry`]
+///
+/// Every report takes the registry's statistics and resets them, so each report covers the window
+/// since the previous one. Reports are driven either by a background thread with [`Reporter::spawn`],
+/// or by any runtime's timer with [`Reporter::run`].
+///
+/// ```rust
+/// # use async_instrumenter::Reporter;
+/// # use log::Level;
+/// # use std::time::Duration;
+/// // on a background thread, stopped when the handle is dropped
+/// let handle = Reporter::new().top(5).level(Level::Info).spawn(Duration::from_secs(60))?;
+/// # handle.stop();
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// ```rust
+/// # use async_instrumenter::Reporter;
+/// # use std::time::Duration;
+/// # async fn sleep(_: Duration) {}
+/// # async fn run() {
+/// // or from a task, with a tick future of the runtime in use
+/// Reporter::new().run(|| sleep(Duration::from_secs(60))).await;
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Reporter {
+    registry: &'static Registry,
+    top: usize,
+    level: Level,
+    window_start: Instant,
+}
+
+impl Reporter {
+    /// A reporter for the [global registry](Registry::global), listing the top 10 sites at info level
+    pub fn new() -> Self {
+        Self {
+            registry: Registry::global(),
+            top: 10,
+            level: Level::Info,
+            window_start: Instant::now(),
+        }
+    }
+
+    /// Report on `registry` instead of the global one
+    pub fn registry(mut self, registry: &'static Registry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// How many sites to list in each table
+    pub fn top(mut self, top: usize) -> Self {
+        self.top = top;
+        self
+    }
+
+    /// The level to log the reports at
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Log a report of the window since the previous report and start a new window
+    ///
+    /// Nothing is logged if no site completed in the window.
+    pub fn report(&mut self) {
+        let now = Instant::now();
+        let window = now - self.window_start;
+        self.window_start = now;
+
+        let mut sites = self.registry.snapshot_and_reset().sites;
+        if sites.is_empty() {
+            return;
+        }
+
+        let mut report = format!("instrumented sites over the last {window:.2?}");
+
+        sites.sort_by_key(|site| Reverse(site.p99));
+        table(
+            &mut report,
+            "slowest (by p99)",
+            &sites[..self.top.min(sites.len())],
+        );
+
+        sites.sort_by_key(|site| Reverse(site.count));
+        table(
+            &mut report,
+            "most frequent",
+            &sites[..self.top.min(sites.len())],
+        );
+
+        emit::report(self.level, &report);
+    }
+
+    /// Report every `interval` on a background thread
+    ///
+    /// Fails if the thread couldn't be spawned, or if the interval is zero.
+    pub fn spawn(mut self, interval: Duration) -> io::Result<BackgroundHandle> {
+        self.window_start = Instant::now();
+        background::spawn_periodic("async-instrumenter-reporter", interval, move || {
+            self.report()
+        })
+    }
+
+    /// Report each time the future returned by `tick` completes, forever
+    pub async fn run<F: Future>(mut self, mut tick: impl FnMut() -> F) {
+        self.window_start = Instant::now();
+
+        loop {
+            tick().await;
+            self.report();
+        }
+    }
+}
+
+impl Default for Reporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn table(report: &mut String, title: &str, sites: &[SiteSummary]) {
+    let names = sites
+        .iter()
+        .map(|site| {
+            if site.labels.is_empty() {
+                site.key.clone()
+            } else {
+                format!("{}{}", site.key, Labels(&site.labels))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let width = names.iter().map(String::len).max().unwrap_or(0).max(4);
+
+    let _ = write!(
+        report,
+        "\n{title}:\n  {:<width$} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "site", "count", "p50", "p90", "p99", "p999", "max"
+    );
+
+    for (site, name) in sites.iter().zip(&names) {
+        let _ = write!(
+            report,
+            "\n  {:<width$} {:>8} {:>10.2?} {:>10.2?} {:>10.2?} {:>10.2?} {:>10.2?}",
+            name, site.count, site.p50, site.p90, site.p99, site.p999, site.max
+        );
+    }
+}
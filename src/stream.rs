@@ -0,0 +1,309 @@
+use std::{
+    fmt::{self, Debug},
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_core::Stream;
+use pin_project::{pin_project, pinned_drop};
+
+use crate::Clock;
+
+/// The timing of an [`InstrumentStream`]
+///
+/// Passed to the callbacks registered with [`InstrumentStream::on_complete`] and [`InstrumentStream::on_cancel`]
+#[derive(Debug, Clone)]
+pub struct InstrumentStreamResult {
+    /// The name given with [`InstrumentStream::name`], if any
+    pub name: Option<&'static str>,
+    /// Wall-clock time from the first poll until the stream ended (or was dropped)
+    pub elapsed: Duration,
+    /// Time from the first poll until the first item was yielded, `None` if no item was yielded
+    pub time_to_first_item: Option<Duration>,
+    /// How many items the stream yielded
+    pub items: u64,
+    /// The shortest time between two consecutive items
+    pub min_gap: Duration,
+    /// The longest time between two consecutive items
+    pub max_gap: Duration,
+    /// The average time between two consecutive items
+    pub mean_gap: Duration,
+    /// Cumulative time spent inside the wrapped stream's `poll_next`
+    pub busy: Duration,
+    /// How many times the wrapped stream was polled
+    pub polls: u64,
+    /// The threshold configured with [`InstrumentStream::threshold`], if any
+    pub threshold: Option<Duration>,
+}
+
+impl InstrumentStreamResult {
+    /// Whether the stream took longer than its [`threshold`](InstrumentStreamResult::threshold)
+    ///
+    /// Always `true` if no threshold was configured
+    pub fn is_slow(&self) -> bool {
+        self.threshold
+            .is_none_or(|threshold| self.elapsed > threshold)
+    }
+}
+
+type Callback = Box<dyn FnOnce(InstrumentStreamResult) + Send + Sync>;
+
+/// Wraps a stream and measures how long it takes to yield its items
+///
+/// Items are passed through unchanged. Once the stream ends, the [`on_complete`](InstrumentStream::on_complete)
+/// callback receives the time to the first item, the gaps between items, the item count and the total
+/// lifetime of the stream. If it's dropped after it was polled but before it ended,
+/// [`on_cancel`](InstrumentStream::on_cancel) is called instead.
+///
+/// ```rust
+/// # use async_instrumenter::{InstrumentStream, MockClock};
+/// # use futures_core::Stream;
+/// # use std::{pin::{pin, Pin}, sync::mpsc, task::{Context, Poll, Waker}, time::Duration};
+/// // yields 3 pages, each taking 10ms to fetch
+/// struct Pages(u32);
+///
+/// impl Stream for Pages {
+///     type Item = u32;
+///
+///     fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<u32>> {
+///         MockClock::advance(Duration::from_millis(10));
+///
+///         self.0 += 1;
+///         Poll::Ready((self.0 <= 3).then_some(self.0))
+///     }
+/// }
+///
+/// let (tx, rx) = mpsc::channel();
+///
+/// let stream = InstrumentStream::<_, MockClock>::with_clock(Pages(0)).on_complete(move |res| {
+///     tx.send(res).unwrap();
+/// });
+///
+/// let mut stream = pin!(stream);
+/// let mut cx = Context::from_waker(Waker::noop());
+/// while let Poll::Ready(Some(_)) = stream.as_mut().poll_next(&mut cx) {}
+///
+/// let res = rx.recv().unwrap();
+/// assert_eq!(res.items, 3);
+/// assert_eq!(res.time_to_first_item, Some(Duration::from_millis(10)));
+/// assert_eq!(res.max_gap, Duration::from_millis(10));
+/// assert_eq!(res.elapsed, Duration::from_millis(40));
+/// ```
+///
+/// Like [`InstrumentFuture`](crate::InstrumentFuture), any [`Clock`] can be used through
+/// [`InstrumentStream::with_clock`].
+#[pin_project(PinnedDrop)]
+pub struct InstrumentStream<S: Stream, C: Clock = Instant> {
+    #[pin]
+    stream: S,
+    stats: StreamStats<C>,
+    on_complete: Option<Callback>,
+    on_cancel: Option<Callback>,
+    done: bool,
+}
+
+impl<S: Stream> InstrumentStream<S> {
+    pub fn new(stream: S) -> Self {
+        Self::with_clock(stream)
+    }
+}
+
+impl<S: Stream, C: Clock> InstrumentStream<S, C> {
+    /// Create an instrumenting stream which measures time with the clock `C`
+    pub fn with_clock(stream: S) -> Self {
+        Self {
+            stream,
+            stats: StreamStats::new(),
+            on_complete: None,
+            on_cancel: None,
+            done: false,
+        }
+    }
+
+    /// Give the stream a name to identify it by in reports
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.stats.name = Some(name);
+        self
+    }
+
+    /// Only consider the stream slow if it took longer than `threshold` to end
+    ///
+    /// See [`InstrumentFuture::threshold`](crate::InstrumentFuture::threshold), the callbacks are
+    /// skipped for streams which ended or got dropped before reaching it.
+    pub fn threshold(mut self, threshold: Duration) -> Self {
+        self.stats.threshold = Some(threshold);
+        self
+    }
+
+    /// Call `callback` once the stream ended
+    pub fn on_complete(
+        mut self,
+        callback: impl FnOnce(InstrumentStreamResult) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_complete = Some(Box::new(callback));
+        self
+    }
+
+    /// Call `callback` if the stream is dropped before it ended
+    ///
+    /// Streams which were never polled weren't cut short, so dropping them doesn't count.
+    pub fn on_cancel(
+        mut self,
+        callback: impl FnOnce(InstrumentStreamResult) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_cancel = Some(Box::new(callback));
+        self
+    }
+}
+
+impl<S: Stream + Debug, C: Clock + Debug> Debug for InstrumentStream<S, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstrumentStream")
+            .field("stream", &self.stream)
+            .field("stats", &self.stats)
+            .field("on_complete", &self.on_complete.as_ref().map(|_| ".."))
+            .field("on_cancel", &self.on_cancel.as_ref().map(|_| ".."))
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl<S: Stream, C: Clock> Stream for InstrumentStream<S, C> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        let start = C::now();
+        this.stats.first_poll.get_or_insert(start);
+
+        let poll = this.stream.poll_next(cx);
+
+        let end = C::now();
+        this.stats.busy += end.duration_since(start);
+        this.stats.polls += 1;
+
+        match &poll {
+            Poll::Ready(Some(_)) => this.stats.item(end),
+
+            Poll::Ready(None) if !*this.done => {
+                *this.done = true;
+
+                let res = this.stats.result(end);
+                if let Some(callback) = this.on_complete.take().filter(|_| res.is_slow()) {
+                    callback(res);
+                }
+            }
+
+            _ => (),
+        }
+
+        poll
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+#[pinned_drop]
+impl<S: Stream, C: Clock> PinnedDrop for InstrumentStream<S, C> {
+    fn drop(self: Pin<&mut Self>) {
+        let this = self.project();
+
+        // a panic unwinding through the stream isn't a cancellation, and neither is
+        // dropping a stream which never got polled
+        if *this.done || std::thread::panicking() || this.stats.first_poll.is_none() {
+            return;
+        }
+
+        let res = this.stats.result(C::now());
+        if let Some(callback) = this.on_cancel.take().filter(|_| res.is_slow()) {
+            callback(res);
+        }
+    }
+}
+
+/// Bookkeeping of the items yielded by an [`InstrumentStream`]
+#[derive(Debug)]
+struct StreamStats<C> {
+    name: Option<&'static str>,
+    threshold: Option<Duration>,
+    first_poll: Option<C>,
+    last_item: Option<C>,
+    time_to_first_item: Option<Duration>,
+    items: u64,
+    gaps: Duration,
+    min_gap: Duration,
+    max_gap: Duration,
+    busy: Duration,
+    polls: u64,
+}
+
+impl<C: Clock> StreamStats<C> {
+    fn new() -> Self {
+        Self {
+            name: None,
+            threshold: None,
+            first_poll: None,
+            last_item: None,
+            time_to_first_item: None,
+            items: 0,
+            gaps: Duration::ZERO,
+            min_gap: Duration::MAX,
+            max_gap: Duration::ZERO,
+            busy: Duration::ZERO,
+            polls: 0,
+        }
+    }
+
+    fn item(&mut self, at: C) {
+        self.items += 1;
+
+        match self.last_item {
+            Some(last_item) => {
+                let gap = at.duration_since(last_item);
+
+                self.gaps += gap;
+                self.min_gap = self.min_gap.min(gap);
+                self.max_gap = self.max_gap.max(gap);
+            }
+
+            None => {
+                self.time_to_first_item = self
+                    .first_poll
+                    .map(|first_poll| at.duration_since(first_poll));
+            }
+        }
+
+        self.last_item = Some(at);
+    }
+
+    fn result(&self, end: C) -> InstrumentStreamResult {
+        let gaps = self.items.saturating_sub(1);
+
+        InstrumentStreamResult {
+            name: self.name,
+            elapsed: self
+                .first_poll
+                .map_or(Duration::ZERO, |first_poll| end.duration_since(first_poll)),
+            time_to_first_item: self.time_to_first_item,
+            items: self.items,
+            min_gap: if gaps == 0 {
+                Duration::ZERO
+            } else {
+                self.min_gap
+            },
+            max_gap: self.max_gap,
+            mean_gap: if gaps == 0 {
+                Duration::ZERO
+            } else {
+                self.gaps.div_f64(gaps as f64)
+            },
+            busy: self.busy,
+            polls: self.polls,
+            threshold: self.threshold,
+        }
+    }
+}
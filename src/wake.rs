@@ -0,0 +1,159 @@
+use std::{
+    fmt::{self, Debug},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    task::{Wake, Waker},
+    time::Duration,
+};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::Clock;
+
+/// How an [`InstrumentFuture`](crate::InstrumentFuture) was woken, see [`InstrumentFuture::track_wakes`](crate::InstrumentFuture::track_wakes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct WakeStats {
+    /// How many times the wrapped future's waker was woken
+    pub wakes: u64,
+    /// How many polls (after the first one) followed a wake
+    pub woken_polls: u64,
+    /// How many polls (after the first one) happened without a wake since the previous poll
+    ///
+    /// Those are polls the future didn't ask for, e.g. by a `select!` polling all of its branches,
+    /// or an executor polling more eagerly than needed.
+    pub spurious_polls: u64,
+    /// The longest time from a wake until the following poll
+    pub max_wake_latency: Duration,
+    /// The average time from a wake until the following poll
+    pub mean_wake_latency: Duration,
+}
+
+/// The poll side of wake tracking
+pub(crate) struct WakeTracker {
+    shared: Arc<dyn Shared>,
+    woken_polls: u64,
+    spurious_polls: u64,
+    total_latency: Duration,
+    max_latency: Duration,
+}
+
+impl WakeTracker {
+    pub(crate) fn new<C: Clock + Send + Sync + 'static>() -> Self {
+        Self {
+            shared: Arc::new(Woken::<C> {
+                wakes: AtomicU64::new(0),
+                woken_at: Mutex::new(None),
+                inner: Mutex::new(Waker::noop().clone()),
+            }),
+            woken_polls: 0,
+            spurious_polls: 0,
+            total_latency: Duration::ZERO,
+            max_latency: Duration::ZERO,
+        }
+    }
+
+    /// Account for a poll starting, and get the waker to pass on to the wrapped future instead of `inner`
+    pub(crate) fn poll(&mut self, inner: &Waker, first: bool) -> Waker {
+        match self.shared.take_latency() {
+            Some(latency) => {
+                self.woken_polls += 1;
+                self.total_latency += latency;
+                self.max_latency = self.max_latency.max(latency);
+            }
+            None if !first => self.spurious_polls += 1,
+            None => (),
+        }
+
+        self.shared.clone().waker(inner)
+    }
+
+    pub(crate) fn stats(&self) -> WakeStats {
+        WakeStats {
+            wakes: self.shared.wakes(),
+            woken_polls: self.woken_polls,
+            spurious_polls: self.spurious_polls,
+            max_wake_latency: self.max_latency,
+            mean_wake_latency: if self.woken_polls == 0 {
+                Duration::ZERO
+            } else {
+                self.total_latency.div_f64(self.woken_polls as f64)
+            },
+        }
+    }
+}
+
+impl Debug for WakeTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.stats().fmt(f)
+    }
+}
+
+/// The wake side of wake tracking, type erased so the clock doesn't need to be `Send + Sync` for
+/// futures which don't track wakes
+trait Shared: Send + Sync {
+    fn waker(self: Arc<Self>, inner: &Waker) -> Waker;
+
+    /// The time since the first wake after the previous poll, if there was one
+    fn take_latency(&self) -> Option<Duration>;
+
+    fn wakes(&self) -> u64;
+}
+
+struct Woken<C> {
+    wakes: AtomicU64,
+    woken_at: Mutex<Option<C>>,
+    inner: Mutex<Waker>,
+}
+
+impl<C: Clock + Send + Sync + 'static> Shared for Woken<C> {
+    fn waker(self: Arc<Self>, inner: &Waker) -> Waker {
+        {
+            let mut current = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+            if !current.will_wake(inner) {
+                current.clone_from(inner);
+            }
+        }
+
+        Waker::from(self)
+    }
+
+    fn take_latency(&self) -> Option<Duration> {
+        let woken_at = self
+            .woken_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+
+        woken_at.map(|woken_at| C::now().duration_since(woken_at))
+    }
+
+    fn wakes(&self) -> u64 {
+        self.wakes.load(Ordering::Relaxed)
+    }
+}
+
+impl<C: Clock + Send + Sync + 'static> Wake for Woken<C> {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wakes.fetch_add(1, Ordering::Relaxed);
+        self.woken_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert_with(C::now);
+
+        // don't hold the lock while waking, the executor might poll right away
+        let inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        inner.wake();
+    }
+}
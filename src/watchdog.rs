@@ -0,0 +1,80 @@
+use std::{io, time::Duration};
+
+use log::Level;
+
+use crate::{
+    background::{self, BackgroundHandle},
+    emit, InFlight,
+};
+
+/// Periodically reports instrumented futures which have been pending for too long
+///
+/// Those are the futures which never complete, and so never get reported otherwise. The watchdog
+/// [enables](InFlight::enable) the tracking of [in-flight](InFlight) futures, and every interval logs
+/// each one older than the maximum age with its name, creation site, poll count and time since its
+/// last poll. Futures which stay stuck get reported again every interval.
+///
+/// ```rust
+/// # use async_instrumenter::Watchdog;
+/// # use log::Level;
+/// # use std::time::Duration;
+/// let handle = Watchdog::new(Duration::from_secs(30))
+///     .interval(Duration::from_secs(10))
+///     .level(Level::Error)
+///     .spawn()?;
+/// # handle.stop();
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    max_age: Duration,
+    interval: Duration,
+    level: Level,
+}
+
+impl Watchdog {
+    /// A watchdog for futures pending longer than `max_age`, checking as often as that at warn level
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            interval: max_age,
+            level: Level::Warn,
+        }
+    }
+
+    /// How often to check for stuck futures
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The level to report stuck futures at
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Report every stuck future once
+    pub fn check(&self) {
+        for future in InFlight::global().snapshot() {
+            if future.age > self.max_age {
+                emit::stuck(self.level, &future);
+            }
+        }
+    }
+
+    /// Check every interval on a background thread, until the returned handle stops it
+    ///
+    /// Fails if the thread couldn't be spawned, or if the interval is zero. Tracking of
+    /// [in-flight](InFlight) futures stays enabled after the watchdog stopped.
+    pub fn spawn(self) -> io::Result<BackgroundHandle> {
+        let interval = self.interval;
+        let handle =
+            background::spawn_periodic("async-instrumenter-watchdog", interval, move || {
+                self.check()
+            })?;
+
+        InFlight::global().enable();
+        Ok(handle)
+    }
+}
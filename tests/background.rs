@@ -0,0 +1,23 @@
+use std::{io, time::Duration};
+
+use async_instrumenter::{Reporter, Watchdog};
+
+#[test]
+fn zero_intervals_are_rejected() {
+    let err = Reporter::new().spawn(Duration::ZERO).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let err = Watchdog::new(Duration::from_secs(1))
+        .interval(Duration::ZERO)
+        .spawn()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn stopping_joins_the_thread() {
+    Reporter::new()
+        .spawn(Duration::from_secs(3600))
+        .unwrap()
+        .stop();
+}
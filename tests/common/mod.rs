@@ -0,0 +1,68 @@
+//! Helpers shared by the integration tests, not every test crate uses all of them
+#![allow(dead_code)]
+
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Mutex,
+    task::{Context, Poll, Waker},
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+struct CapturingLogger(Mutex<Vec<(String, Level, String)>>);
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.0.lock().unwrap().push((
+            record.target().to_owned(),
+            record.level(),
+            record.args().to_string(),
+        ));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+
+/// Install the capturing logger and take what was logged under `target` so far
+///
+/// Tests run in parallel, so each should log under its own target.
+pub fn logged(target: &str) -> Vec<(Level, String)> {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(LevelFilter::Trace);
+
+    let mut logged = LOGGER.0.lock().unwrap();
+    let (ours, rest) = logged.drain(..).partition(|(t, _, _)| t == target);
+    *logged = rest;
+
+    ours.into_iter()
+        .map(|(_, level, message)| (level, message))
+        .collect()
+}
+
+/// A context whose waker does nothing
+pub fn cx() -> Context<'static> {
+    Context::from_waker(Waker::noop())
+}
+
+/// Poll `fut` once and drop it
+pub fn poll_once<F: Future>(fut: F) -> Poll<F::Output> {
+    pin!(fut).poll(&mut cx())
+}
+
+/// Poll `fut` until it's ready
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+
+    loop {
+        if let Poll::Ready(out) = fut.as_mut().poll(&mut cx()) {
+            return out;
+        }
+    }
+}
@@ -0,0 +1,25 @@
+// the `log` messages are checked, which `tracing` replaces with events
+#![cfg(not(feature = "tracing"))]
+
+mod common;
+
+use std::future::pending;
+
+use async_instrumenter::InstrumentExt;
+use common::{logged, poll_once};
+use log::Level;
+
+#[test]
+fn log_elapsed_only_reports_cancellations_after_polling() {
+    logged("async_instrumenter");
+
+    drop(pending::<()>().log_elapsed(Level::Info));
+    assert!(logged("async_instrumenter").is_empty());
+
+    assert!(poll_once(pending::<()>().log_elapsed(Level::Info)).is_pending());
+
+    let logged = logged("async_instrumenter");
+    assert_eq!(logged.len(), 1);
+    assert!(logged[0].1.contains("cancelled after"), "{logged:?}");
+    assert!(logged[0].1.contains("(1 polls)"), "{logged:?}");
+}
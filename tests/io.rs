@@ -0,0 +1,134 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_instrumenter::{InstrumentIo, MockClock};
+use common::cx;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Takes 2ms per call, and is optionally not ready the first time it's read from
+struct Mock {
+    pending_first: bool,
+}
+
+impl AsyncRead for Mock {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        MockClock::advance(Duration::from_millis(2));
+
+        if std::mem::take(&mut self.pending_first) {
+            return Poll::Pending;
+        }
+
+        buf.put_slice(b"hello");
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Mock {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        MockClock::advance(Duration::from_millis(2));
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[test]
+fn counts_only_the_newly_read_bytes() {
+    let mut io = InstrumentIo::<_, MockClock>::with_clock(Mock {
+        pending_first: false,
+    });
+
+    let mut storage = [0; 16];
+    let mut buf = ReadBuf::new(&mut storage);
+    buf.put_slice(b"abc");
+
+    let res = Pin::new(&mut io).poll_read(&mut cx(), &mut buf);
+    assert!(matches!(res, Poll::Ready(Ok(()))));
+    assert_eq!(buf.filled(), b"abchello");
+
+    let stats = io.stats();
+    assert_eq!(stats.bytes_read, 5);
+    assert_eq!(stats.bytes_written, 0);
+}
+
+#[test]
+fn tracks_pending_and_blocked_time() {
+    let mut io = InstrumentIo::<_, MockClock>::with_clock(Mock {
+        pending_first: true,
+    });
+
+    let mut storage = [0; 16];
+    let mut buf = ReadBuf::new(&mut storage);
+
+    assert!(Pin::new(&mut io)
+        .poll_read(&mut cx(), &mut buf)
+        .is_pending());
+    MockClock::advance(Duration::from_millis(10));
+    assert!(Pin::new(&mut io).poll_read(&mut cx(), &mut buf).is_ready());
+
+    let stats = io.stats();
+    assert_eq!(stats.polls, 2);
+    assert_eq!(stats.pending, 1);
+    assert_eq!(stats.busy, Duration::from_millis(4));
+    assert_eq!(stats.blocked, Duration::from_millis(10));
+    assert_eq!(stats.elapsed, Duration::from_millis(14));
+    assert_eq!(stats.bytes_read, 5);
+}
+
+#[test]
+fn reports_once_across_shutdown_and_drop() {
+    let reports = Arc::new(AtomicUsize::new(0));
+    let written = Arc::new(AtomicUsize::new(0));
+
+    let mut io = InstrumentIo::<_, MockClock>::with_clock(Mock {
+        pending_first: false,
+    })
+    .on_report({
+        let reports = Arc::clone(&reports);
+        let written = Arc::clone(&written);
+        move |res| {
+            reports.fetch_add(1, Ordering::Relaxed);
+            written.store(res.bytes_written as usize, Ordering::Relaxed);
+        }
+    });
+
+    assert!(matches!(
+        Pin::new(&mut io).poll_write(&mut cx(), b"hello world"),
+        Poll::Ready(Ok(11))
+    ));
+    assert!(matches!(
+        Pin::new(&mut io).poll_shutdown(&mut cx()),
+        Poll::Ready(Ok(()))
+    ));
+    assert_eq!(reports.load(Ordering::Relaxed), 1);
+    assert_eq!(written.load(Ordering::Relaxed), 11);
+
+    drop(io);
+    assert_eq!(reports.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use std::time::Duration;
+
+use async_instrumenter::{InstrumentFuture, Registry};
+use common::block_on;
+
+#[test]
+fn reserved_label_keys_are_kept_until_recorded() {
+    let res = block_on(InstrumentFuture::new(async {}).label("le", 1));
+    assert_eq!(res.labels, [("le", "1".to_owned())]);
+}
+
+#[test]
+#[should_panic(expected = "label key `le` is reserved")]
+fn reserved_label_keys_are_rejected() {
+    let res = block_on(InstrumentFuture::new(async {}).label("le", 1));
+    Registry::new().record_result(&res);
+}
+
+#[test]
+#[should_panic(expected = "invalid label key `tenant-id`")]
+fn invalid_label_keys_are_rejected() {
+    let res = block_on(InstrumentFuture::new(async {}).labels([("tenant-id", 1)]));
+    Registry::new().record_result(&res);
+}
+
+#[test]
+#[should_panic(expected = "invalid label key `__name__`")]
+fn prefixed_label_keys_are_rejected() {
+    Registry::new().record_labeled("fetch_user", &[("__name__", "x")], Duration::ZERO);
+}
@@ -0,0 +1,138 @@
+// the `log` messages are checked, which `tracing` replaces with events
+#![cfg(not(feature = "tracing"))]
+
+mod common;
+
+use std::{
+    future::{pending, Future},
+    pin::pin,
+    task::Poll,
+    thread,
+    time::Duration,
+};
+
+use async_instrumenter::instrument;
+use common::{cx, logged, poll_once};
+use log::Level;
+
+#[test]
+fn target_and_level_are_evaluated_once() {
+    logged("borrowed_target");
+    let target = String::from("borrowed_target");
+    let mut evaluated = 0;
+
+    let res = poll_once(instrument!(
+        target: &target,
+        {
+            evaluated += 1;
+            Level::Info
+        },
+        async { 42 }
+    ));
+
+    assert_eq!(res, Poll::Ready(42));
+    assert_eq!(evaluated, 1);
+    let logged = logged("borrowed_target");
+    assert_eq!(logged.len(), 1);
+    assert_eq!(logged[0].0, Level::Info);
+    assert!(logged[0].1.contains("completed in"), "{logged:?}");
+}
+
+#[test]
+fn cancellation_is_logged_once_dropped() {
+    logged("cancelled_target");
+    let target = String::from("cancelled_target");
+
+    {
+        let mut fut = pin!(instrument!(target: &target, Level::Warn, pending::<()>()));
+        assert!(fut.as_mut().poll(&mut cx()).is_pending());
+        assert!(logged("cancelled_target").is_empty());
+    }
+
+    let logged = logged("cancelled_target");
+    assert_eq!(logged.len(), 1);
+    assert_eq!(logged[0].0, Level::Warn);
+    assert!(logged[0].1.contains("cancelled after"), "{logged:?}");
+    assert!(logged[0].1.contains("(1 polls)"), "{logged:?}");
+}
+
+/// A future taking at least a millisecond, so it's above a zero threshold
+async fn slow() {
+    thread::sleep(Duration::from_millis(1));
+}
+
+#[test]
+fn threshold_silences_fast_futures() {
+    logged("threshold");
+
+    let _ = poll_once(instrument!(
+        target: "threshold",
+        threshold = Duration::from_secs(3600),
+        slow()
+    ));
+    assert!(logged("threshold").is_empty());
+
+    let _ = poll_once(instrument!(target: "threshold", threshold = Duration::ZERO, slow()));
+    let logged = logged("threshold");
+    assert_eq!(logged.len(), 1);
+    assert_eq!(logged[0].0, Level::Debug);
+}
+
+#[test]
+fn escalate_switches_the_level() {
+    logged("escalate");
+
+    for above in [Duration::from_secs(3600), Duration::ZERO] {
+        let _ = poll_once(instrument!(
+            target: "escalate",
+            Level::Info,
+            threshold = Duration::ZERO,
+            escalate = (Level::Error, above),
+            slow()
+        ));
+    }
+
+    let levels: Vec<_> = logged("escalate")
+        .into_iter()
+        .map(|(level, _)| level)
+        .collect();
+    assert_eq!(levels, [Level::Info, Level::Error]);
+}
+
+#[test]
+fn threshold_silences_fast_cancellations() {
+    logged("cancelled_threshold");
+
+    let mut fut = Box::pin(instrument!(
+        target: "cancelled_threshold",
+        threshold = Duration::from_secs(3600),
+        pending::<()>()
+    ));
+    let _ = fut.as_mut().poll(&mut cx());
+    drop(fut);
+
+    assert!(logged("cancelled_threshold").is_empty());
+}
+
+#[test]
+fn custom_messages_are_followed_by_the_labels() {
+    logged("custom_message");
+
+    let tenant = 42;
+    let res = poll_once(instrument!(
+        target: "custom_message",
+        labels = [tenant = tenant],
+        "fetched in {elapsed:?}",
+        async {}
+    ));
+    assert!(res.is_ready());
+
+    let res = poll_once(instrument!(target: "custom_message", "fetched in {elapsed:?}", async {}));
+    assert!(res.is_ready());
+
+    let logged = logged("custom_message");
+    assert_eq!(logged.len(), 2);
+    assert!(logged[0].1.starts_with("fetched in "), "{logged:?}");
+    assert!(logged[0].1.ends_with(r#" {tenant="42"}"#), "{logged:?}");
+    assert!(!logged[1].1.contains('{'), "{logged:?}");
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "metrics")]
+
+mod common;
+
+use std::{future::pending, task::Poll, time::Duration};
+
+use async_instrumenter::instrument;
+use common::poll_once;
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+#[test]
+fn completions_and_cancellations_are_recorded() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    let (completed_line, cancelled_line) = metrics::with_local_recorder(&recorder, || {
+        let completed_line = line!() + 1;
+        let res = poll_once(instrument!(
+            name = "fetch_user",
+            labels = [tenant = 42],
+            async { 1 }
+        ));
+        assert_eq!(res, Poll::Ready(1));
+
+        let cancelled_line = line!() + 1;
+        let res = poll_once(instrument!(name = "fetch_user", pending::<()>()));
+        assert!(res.is_pending());
+
+        (completed_line, cancelled_line)
+    });
+
+    let mut recorded = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| {
+            let (_, key) = key.into_parts();
+            let labels = key
+                .labels()
+                .map(|label| (label.key().to_owned(), label.value().to_owned()))
+                .collect::<Vec<_>>();
+
+            let DebugValue::Histogram(values) = value else {
+                panic!("{} isn't a histogram", key.name());
+            };
+
+            (key.name().to_owned(), labels, values.len())
+        })
+        .collect::<Vec<_>>();
+    recorded.sort();
+
+    let labels = |outcome: &str, line: u32, extra: &[(&str, &str)]| {
+        [
+            ("name", "fetch_user"),
+            ("outcome", outcome),
+            ("file", file!()),
+            ("line", &line.to_string()),
+        ]
+        .iter()
+        .chain(extra)
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        recorded,
+        [
+            (
+                "async_instrumenter_duration_seconds".to_owned(),
+                labels("cancelled", cancelled_line, &[]),
+                1
+            ),
+            (
+                "async_instrumenter_duration_seconds".to_owned(),
+                labels("completed", completed_line, &[("tenant", "42")]),
+                1
+            ),
+        ]
+    );
+}
+
+#[test]
+fn cancellations_below_the_threshold_are_recorded() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    metrics::with_local_recorder(&recorder, || {
+        let res = poll_once(instrument!(
+            threshold = Duration::from_secs(3600),
+            pending::<()>()
+        ));
+        assert!(res.is_pending());
+    });
+
+    let outcomes = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .flat_map(|(key, _, _, _)| {
+            let (_, key) = key.into_parts();
+            key.labels()
+                .filter(|label| label.key() == "outcome")
+                .map(|label| label.value().to_owned())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(outcomes, ["cancelled"]);
+}
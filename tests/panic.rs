@@ -0,0 +1,68 @@
+mod common;
+
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::pin,
+    sync::mpsc,
+};
+
+use async_instrumenter::InstrumentFuture;
+use common::cx;
+
+#[test]
+fn nested_futures_all_see_the_panic_location() {
+    let (tx, rx) = mpsc::channel();
+    let outer_tx = tx.clone();
+
+    let line = line!() + 1;
+    let inner = InstrumentFuture::new(async { panic!("boom") })
+        .name("inner")
+        .on_panic(move |panicked| tx.send(panicked).unwrap());
+
+    let outer = InstrumentFuture::new(inner)
+        .name("outer")
+        .label("tenant", 42)
+        .on_panic(move |panicked| outer_tx.send(panicked).unwrap());
+
+    let mut outer = pin!(outer);
+    let res = panic::catch_unwind(AssertUnwindSafe(|| outer.as_mut().poll(&mut cx())));
+    assert!(res.is_err());
+
+    let inner = rx.recv().unwrap();
+    assert_eq!(inner.name, Some("inner"));
+    assert!(inner.labels.is_empty());
+    assert_eq!(inner.location.line(), line);
+
+    let outer = rx.recv().unwrap();
+    assert_eq!(outer.name, Some("outer"));
+    assert_eq!(outer.labels, [("tenant", "42".to_owned())]);
+    assert_eq!(outer.location.line(), line + 4);
+
+    for panicked in [inner, outer] {
+        let panic_location = panicked.panic_location.unwrap();
+        assert_eq!(panic_location.file, file!());
+        assert_eq!(panic_location.line, line);
+        assert_eq!(panicked.message.as_deref(), Some("boom"));
+    }
+}
+
+#[test]
+fn stale_locations_are_not_reported() {
+    let _ = panic::catch_unwind(|| panic!("unrelated"));
+
+    let (tx, rx) = mpsc::channel();
+    let fut = InstrumentFuture::new(async {
+        // not raised through `panic!`, so the hook records no location for it
+        panic::resume_unwind(Box::new("resumed"))
+    })
+    .on_panic(move |panicked| tx.send(panicked).unwrap());
+
+    let mut fut = pin!(fut);
+    let res = panic::catch_unwind(AssertUnwindSafe(|| fut.as_mut().poll(&mut cx())));
+    assert!(res.is_err());
+
+    let panicked = rx.recv().unwrap();
+    assert_eq!(panicked.panic_location, None);
+    assert_eq!(panicked.message.as_deref(), Some("resumed"));
+}
@@ -0,0 +1,45 @@
+// the `log` messages are checked, which `tracing` replaces with events
+#![cfg(not(feature = "tracing"))]
+
+mod common;
+
+use std::{future::poll_fn, task::Poll, time::Duration};
+
+use async_instrumenter::{InstrumentFuture, MockClock};
+use common::{logged, poll_once};
+use log::Level;
+
+/// Run a future whose single poll takes `poll` against a budget of 10ms, returning its creation line
+fn poll_taking(poll: Duration) -> u32 {
+    let fut = poll_fn(|_| {
+        MockClock::advance(poll);
+        Poll::Ready(())
+    });
+
+    let line = line!() + 1;
+    let fut = InstrumentFuture::<_, MockClock>::with_clock(fut)
+        .name("blocking")
+        .poll_budget(Duration::from_millis(10));
+    assert!(poll_once(fut).is_ready());
+
+    line
+}
+
+#[test]
+fn only_polls_over_budget_are_reported() {
+    logged("async_instrumenter");
+
+    poll_taking(Duration::from_millis(5));
+    assert!(logged("async_instrumenter").is_empty());
+
+    let line = poll_taking(Duration::from_millis(20));
+    let logged = logged("async_instrumenter");
+    assert_eq!(logged.len(), 1);
+
+    let (level, message) = &logged[0];
+    assert_eq!(*level, Level::Warn);
+    assert!(
+        message.starts_with(&format!("blocking ({}:{line}) blocked for 20ms", file!())),
+        "{message}"
+    );
+}
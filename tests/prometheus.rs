@@ -0,0 +1,32 @@
+#![cfg(feature = "prometheus")]
+
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use async_instrumenter::Registry;
+
+#[test]
+fn idle_clients_dont_stall_scrapes() {
+    static REGISTRY: Registry = Registry::new();
+    REGISTRY.record("fetch_user", Duration::from_millis(3));
+
+    let addr = REGISTRY.serve_openmetrics("127.0.0.1:0").unwrap();
+
+    // connects but never sends a request
+    let _idle = TcpStream::connect(addr).unwrap();
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+}
@@ -0,0 +1,63 @@
+// the `log` messages are checked, which `tracing` replaces with events
+#![cfg(not(feature = "tracing"))]
+
+mod common;
+
+use std::time::Duration;
+
+use async_instrumenter::{Registry, Reporter};
+use common::logged;
+use log::Level;
+
+/// The sites listed in the table titled `title`, in order
+fn listed<'a>(report: &'a str, title: &str) -> Vec<&'a str> {
+    report
+        .split_once(&format!("\n{title}:\n"))
+        .unwrap()
+        .1
+        .lines()
+        .skip(1)
+        .take_while(|line| line.starts_with("  "))
+        .map(|line| line.split_whitespace().next().unwrap())
+        .collect()
+}
+
+#[test]
+fn reports_the_top_sites_of_each_window() {
+    static REGISTRY: Registry = Registry::new();
+    let mut reporter = Reporter::new()
+        .registry(&REGISTRY)
+        .top(2)
+        .level(Level::Warn);
+    logged("async_instrumenter");
+
+    // nothing to report yet
+    reporter.report();
+    assert!(logged("async_instrumenter").is_empty());
+
+    for _ in 0..10 {
+        REGISTRY.record("fast", Duration::from_millis(1));
+    }
+    for _ in 0..3 {
+        REGISTRY.record("medium", Duration::from_millis(50));
+    }
+    REGISTRY.record("slow", Duration::from_millis(500));
+
+    reporter.report();
+    let logged_reports = logged("async_instrumenter");
+    assert_eq!(logged_reports.len(), 1);
+
+    let (level, report) = &logged_reports[0];
+    assert_eq!(*level, Level::Warn);
+    assert!(
+        report.starts_with("instrumented sites over the last"),
+        "{report}"
+    );
+    assert_eq!(listed(report, "slowest (by p99)"), ["slow", "medium"]);
+    assert_eq!(listed(report, "most frequent"), ["fast", "medium"]);
+
+    // the window was reset by the report
+    assert!(REGISTRY.snapshot().sites.is_empty());
+    reporter.report();
+    assert!(logged("async_instrumenter").is_empty());
+}
@@ -0,0 +1,11 @@
+#![cfg(all(unix, feature = "signal-hook"))]
+
+use async_instrumenter::InFlight;
+use log::Level;
+
+#[test]
+fn dump_on_sigusr1_can_be_called_again() {
+    InFlight::global().dump_on_sigusr1(Level::Warn).unwrap();
+    InFlight::global().dump_on_sigusr1(Level::Info).unwrap();
+    assert!(InFlight::global().is_enabled());
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "stream")]
+
+mod common;
+
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use async_instrumenter::InstrumentStream;
+use common::cx;
+use futures_core::Stream;
+
+struct Never;
+
+impl Stream for Never {
+    type Item = ();
+
+    fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<()>> {
+        Poll::Pending
+    }
+}
+
+fn counting_cancels(cancels: &Arc<AtomicUsize>) -> InstrumentStream<Never> {
+    let cancels = Arc::clone(cancels);
+    InstrumentStream::new(Never).on_cancel(move |_| {
+        cancels.fetch_add(1, Ordering::Relaxed);
+    })
+}
+
+#[test]
+fn only_polled_streams_are_cancelled() {
+    let cancels = Arc::new(AtomicUsize::new(0));
+
+    drop(counting_cancels(&cancels));
+    assert_eq!(cancels.load(Ordering::Relaxed), 0);
+
+    let mut stream = Box::pin(counting_cancels(&cancels));
+    assert!(stream.as_mut().poll_next(&mut cx()).is_pending());
+    drop(stream);
+    assert_eq!(cancels.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::{
+    future::{pending, Future},
+    pin::pin,
+    sync::mpsc,
+    time::Duration,
+};
+
+use async_instrumenter::{InstrumentFuture, MockClock};
+use common::cx;
+
+fn cancel_after(elapsed: Duration) -> Option<Duration> {
+    let (tx, rx) = mpsc::channel();
+
+    let mut fut = Box::pin(
+        InstrumentFuture::<_, MockClock>::with_clock(pending::<()>())
+            .threshold(Duration::from_millis(50))
+            .on_cancel(move |cancelled| tx.send(cancelled.elapsed).unwrap()),
+    );
+    assert!(fut.as_mut().poll(&mut cx()).is_pending());
+
+    MockClock::advance(elapsed);
+    drop(fut);
+
+    rx.try_recv().ok()
+}
+
+#[test]
+fn threshold_suppresses_on_cancel() {
+    assert_eq!(cancel_after(Duration::from_millis(10)), None);
+    assert_eq!(cancel_after(Duration::from_millis(50)), None);
+    assert_eq!(
+        cancel_after(Duration::from_millis(100)),
+        Some(Duration::from_millis(100))
+    );
+}
+
+#[test]
+fn threshold_marks_completions_as_slow() {
+    for (elapsed, slow) in [(10, false), (50, false), (100, true)] {
+        let fut = std::future::poll_fn(|_| {
+            MockClock::advance(Duration::from_millis(elapsed));
+            std::task::Poll::Ready(())
+        });
+        let fut =
+            pin!(InstrumentFuture::<_, MockClock>::with_clock(fut)
+                .threshold(Duration::from_millis(50)));
+
+        let res = match fut.poll(&mut cx()) {
+            std::task::Poll::Ready(res) => res,
+            std::task::Poll::Pending => unreachable!(),
+        };
+        assert_eq!(res.elapsed, Duration::from_millis(elapsed));
+        assert_eq!(res.is_slow(), slow);
+    }
+}
@@ -0,0 +1,23 @@
+#![cfg(feature = "tracing")]
+
+mod common;
+
+use async_instrumenter::instrument;
+use common::{block_on, logged};
+use log::Level;
+
+/// Without a `tracing` subscriber, events are forwarded to `log` and caught there
+#[test]
+fn level_is_picked_at_runtime() {
+    logged("runtime_level");
+
+    for level in [Level::Warn, Level::Trace] {
+        block_on(instrument!(target: "runtime_level", level, async {}));
+    }
+
+    let levels: Vec<_> = logged("runtime_level")
+        .into_iter()
+        .map(|(level, _)| level)
+        .collect();
+    assert_eq!(levels, [Level::Warn, Level::Trace]);
+}
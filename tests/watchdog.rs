@@ -0,0 +1,41 @@
+// the `log` messages are checked, which `tracing` replaces with events
+#![cfg(not(feature = "tracing"))]
+
+mod common;
+
+use std::{
+    future::{pending, Future},
+    pin::pin,
+    thread,
+    time::Duration,
+};
+
+use async_instrumenter::{InFlight, InstrumentFuture, Watchdog};
+use common::{cx, logged};
+use log::Level;
+
+#[test]
+fn only_futures_older_than_the_max_age_are_reported() {
+    InFlight::global().enable();
+    logged("async_instrumenter");
+
+    let mut stuck = pin!(InstrumentFuture::new(pending::<()>()).name("stuck"));
+    assert!(stuck.as_mut().poll(&mut cx()).is_pending());
+
+    thread::sleep(Duration::from_millis(100));
+
+    let mut young = pin!(InstrumentFuture::new(pending::<()>()).name("young"));
+    assert!(young.as_mut().poll(&mut cx()).is_pending());
+
+    Watchdog::new(Duration::from_millis(50))
+        .level(Level::Error)
+        .check();
+
+    let logged = logged("async_instrumenter");
+    assert_eq!(logged.len(), 1, "{logged:?}");
+
+    let (level, message) = &logged[0];
+    assert_eq!(*level, Level::Error);
+    assert!(message.starts_with("stuck pending for "), "{message}");
+    assert!(message.contains("(1 polls, last poll "), "{message}");
+}